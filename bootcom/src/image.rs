@@ -0,0 +1,44 @@
+//! Loading the image to send from the files produced by the `build` recipe.
+
+use std::{fs, io, path::Path};
+
+use chainload::{elf::Elf, Header};
+
+/// The address at which the firmware loads `kernel.img`, and where flat binaries go.
+const DEFAULT_LOAD_ADDRESS: u32 = 0x8000;
+
+/// An image, ready to be sent to the bootloader.
+pub struct Image {
+    pub bytes: Vec<u8>,
+    pub header: Header,
+    /// Where the bootloader jumps once the image is loaded.
+    pub entry_point: u32,
+}
+
+/// Load a flat binary (`.img`) or an ELF file.
+///
+/// ELF files are sent as is, the bootloader puts their segments in place. They are checked here
+/// so that errors are reported before waiting for the bootloader.
+pub fn load(path: &Path) -> io::Result<Image> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(b"\x7fELF") {
+        let elf = Elf::parse(&bytes).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid ELF file: {err:?}"),
+            )
+        })?;
+        let entry_point = elf.entry_point();
+        Ok(Image {
+            header: Header::elf(&bytes),
+            bytes,
+            entry_point,
+        })
+    } else {
+        Ok(Image {
+            header: Header::flat(&bytes, DEFAULT_LOAD_ADDRESS, DEFAULT_LOAD_ADDRESS),
+            bytes,
+            entry_point: DEFAULT_LOAD_ADDRESS,
+        })
+    }
+}
@@ -0,0 +1,156 @@
+//! Send an image to the bootloader over a serial line, then act as a terminal.
+//!
+//! Usage: `bootcom <port> <image> [baud rate]`
+//!
+//! `<port>` is usually a serial device such as `/dev/tty.usbserial-0001`, but any file that can be
+//! read and written works (such as a pty, which is handy for testing). `<image>` is either the
+//! `.img` or the ELF file produced by the `build` recipe.
+
+mod image;
+mod send;
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
+
+use serialport::SerialPort;
+
+/// The baud rate the bootloader configures the Mini UART with.
+const DEFAULT_BAUD_RATE: u32 = 115200;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    let (port, image, baud_rate) = match args.as_slice() {
+        [_, port, image] => (port, image, Ok(DEFAULT_BAUD_RATE)),
+        [_, port, image, baud_rate] => (port, image, baud_rate.parse()),
+        _ => {
+            eprintln!("usage: bootcom <port> <image> [baud rate]");
+            return ExitCode::FAILURE;
+        }
+    };
+    let Ok(baud_rate) = baud_rate else {
+        eprintln!("invalid baud rate");
+        return ExitCode::FAILURE;
+    };
+
+    match run(Path::new(port), Path::new(image), baud_rate) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(port: &Path, image: &Path, baud_rate: u32) -> io::Result<()> {
+    let image = image::load(image)?;
+    let mut port = Port::open(port, baud_rate)?;
+
+    eprintln!("Waiting for the bootloader...");
+    send::send(&mut port, &image.header, &image.bytes, |sent, total| {
+        eprint!(
+            "\rSending: {sent}/{total} bytes ({}%)",
+            sent * 100 / total.max(1)
+        );
+    })?;
+    eprintln!("\nImage sent, jumping to {:#010x}.", image.entry_point);
+
+    terminal(port)
+}
+
+/// Forward standard input to the port, and the port to standard output.
+fn terminal(mut port: Port) -> io::Result<()> {
+    let mut input = port.try_clone()?;
+    thread::spawn(move || io::copy(&mut io::stdin().lock(), &mut input));
+
+    let mut stdout = io::stdout().lock();
+    let mut buf = [0; 256];
+    loop {
+        match port.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => {
+                stdout.write_all(&buf[..n])?;
+                stdout.flush()?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The line to the bootloader.
+enum Port {
+    Serial(Box<dyn SerialPort>),
+    File(File),
+}
+
+impl Port {
+    /// How long a read waits for data on a serial port.
+    const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+    /// Open `path` once, as a serial port if it is a character device.
+    ///
+    /// The kind of file is decided without opening it, as a blocking open of a serial device can
+    /// wait for carrier detect.
+    fn open(path: &Path, baud_rate: u32) -> io::Result<Self> {
+        if !is_char_device(path)? {
+            let file = OpenOptions::new().read(true).write(true).open(path)?;
+            return Ok(Self::File(file));
+        }
+        let port = serialport::new(path.to_string_lossy(), baud_rate)
+            .timeout(Self::READ_TIMEOUT)
+            .open()?;
+        Ok(Self::Serial(port))
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Serial(port) => Ok(Self::Serial(port.try_clone()?)),
+            Self::File(file) => Ok(Self::File(file.try_clone()?)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_char_device(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::FileTypeExt;
+
+    Ok(fs::metadata(path)?.file_type().is_char_device())
+}
+
+/// Serial ports such as `COM3` are not files.
+#[cfg(not(unix))]
+fn is_char_device(path: &Path) -> io::Result<bool> {
+    Ok(!path.is_file())
+}
+
+impl Read for Port {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Serial(port) => port.read(buf),
+            Self::File(file) => file.read(buf),
+        }
+    }
+}
+
+impl Write for Port {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Serial(port) => port.write(buf),
+            Self::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Serial(port) => port.flush(),
+            Self::File(file) => file.flush(),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! The host side of the chain loading protocol, see the `chainload` crate.
+
+use std::{
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+use chainload::{Chunk, Header, ACK, HELLO, MAX_CHUNK_LEN, NAK};
+
+/// How long the bootloader can take to reply to a frame. It waits for the line to be quiet for
+/// a quarter of a second before rejecting one.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the bootloader can take to check the whole image.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+/// How many times in a row a frame can be rejected before giving up.
+const MAX_RETRIES: u32 = 16;
+
+/// Wait for the bootloader, then send it `image`.
+///
+/// `progress` is called with the number of bytes sent so far and the total number of bytes.
+pub fn send<P: Read + Write>(
+    port: &mut P,
+    header: &Header,
+    image: &[u8],
+    mut progress: impl FnMut(usize, usize),
+) -> io::Result<()> {
+    let mut retries = 0;
+    loop {
+        wait_for_hello(port)?;
+        port.write_all(&header.encode())?;
+        port.flush()?;
+        match reply(port, REPLY_TIMEOUT)? {
+            Some(ACK) => break,
+            Some(_) => retries += 1,
+            None => {}
+        }
+        if retries == MAX_RETRIES {
+            return Err(io::Error::other("the bootloader rejected the header"));
+        }
+    }
+
+    let mut frame = [0; MAX_CHUNK_LEN];
+    let mut sent = 0;
+    progress(sent, image.len());
+    for chunk in Chunk::split(image) {
+        let frame = chunk.encode(&mut frame);
+        let mut retries = 0;
+        loop {
+            port.write_all(frame)?;
+            port.flush()?;
+            if reply(port, REPLY_TIMEOUT)? == Some(ACK) {
+                break;
+            }
+            retries += 1;
+            if retries == MAX_RETRIES {
+                return Err(io::Error::other(format!(
+                    "the bootloader rejected chunk {} too many times",
+                    chunk.index
+                )));
+            }
+        }
+        sent += chunk.data.len();
+        progress(sent, image.len());
+    }
+
+    match reply(port, CHECK_TIMEOUT)? {
+        Some(ACK) => Ok(()),
+        Some(_) => Err(io::Error::other("the bootloader rejected the image")),
+        None => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "the bootloader did not confirm the image",
+        )),
+    }
+}
+
+/// Skip everything until the bootloader says hello.
+fn wait_for_hello<P: Read>(port: &mut P) -> io::Result<()> {
+    let mut window = [0; HELLO.len()];
+    loop {
+        let mut byte = [0];
+        match port.read(&mut byte) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(_) => {
+                window.rotate_left(1);
+                window[HELLO.len() - 1] = byte[0];
+                if window == HELLO {
+                    return Ok(());
+                }
+            }
+            Err(e) if is_timeout(&e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Wait for an [`ACK`] or a [`NAK`], `None` if none came in time.
+fn reply<P: Read>(port: &mut P, timeout: Duration) -> io::Result<Option<u8>> {
+    let start = Instant::now();
+    let mut byte = [0];
+    while start.elapsed() < timeout {
+        match port.read(&mut byte) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(_) if byte[0] == ACK || byte[0] == NAK => return Ok(Some(byte[0])),
+            // Most likely the bootloader saying hello after starting over.
+            Ok(_) => {}
+            Err(e) if is_timeout(&e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use chainload::{crc32, CHUNK_PREFIX_LEN};
+
+    use super::*;
+
+    /// Behaves like the bootloader on the other end of the line.
+    struct FakeBootloader {
+        input: Vec<u8>,
+        output: VecDeque<u8>,
+        header: Option<Header>,
+        image: Vec<u8>,
+        /// Reject the header this many times.
+        reject_header: u32,
+        /// Corrupt the chunk with this index the first time it is received.
+        corrupt_chunk: Option<u32>,
+    }
+
+    impl FakeBootloader {
+        fn new() -> Self {
+            Self {
+                input: Vec::new(),
+                output: HELLO.into_iter().collect(),
+                header: None,
+                image: Vec::new(),
+                reject_header: 0,
+                corrupt_chunk: None,
+            }
+        }
+
+        fn process(&mut self) {
+            let Some(header) = self.header else {
+                let Some(bytes) = self.input.first_chunk::<{ Header::LEN }>() else {
+                    return;
+                };
+                let header = Header::decode(bytes).unwrap();
+                self.input.drain(..Header::LEN);
+                if self.reject_header > 0 {
+                    self.reject_header -= 1;
+                    self.output.push_back(NAK);
+                    self.output.extend(HELLO);
+                } else {
+                    self.header = Some(header);
+                    self.output.push_back(ACK);
+                }
+                return;
+            };
+
+            let Some(prefix) = self.input.first_chunk::<CHUNK_PREFIX_LEN>() else {
+                return;
+            };
+            let frame_len = Chunk::frame_len(prefix).unwrap();
+            if self.input.len() < frame_len {
+                return;
+            }
+            let mut frame: Vec<u8> = self.input.drain(..frame_len).collect();
+            let index = u32::from_le_bytes(frame[..4].try_into().unwrap());
+            if self.corrupt_chunk == Some(index) {
+                self.corrupt_chunk = None;
+                frame[CHUNK_PREFIX_LEN] ^= 0xFF;
+            }
+            match Chunk::decode(&frame) {
+                Ok(chunk) => {
+                    assert_eq!(
+                        chunk.index as usize,
+                        self.image.len() / chainload::CHUNK_SIZE
+                    );
+                    self.image.extend_from_slice(chunk.data);
+                    self.output.push_back(ACK);
+                }
+                Err(_) => self.output.push_back(NAK),
+            }
+            if self.image.len() == header.size as usize {
+                let valid = crc32(&self.image) == header.checksum;
+                self.output.push_back(if valid { ACK } else { NAK });
+            }
+        }
+    }
+
+    impl Read for FakeBootloader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.output.pop_front() {
+                Some(byte) => {
+                    buf[0] = byte;
+                    Ok(1)
+                }
+                None => Err(io::ErrorKind::TimedOut.into()),
+            }
+        }
+    }
+
+    impl Write for FakeBootloader {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.input.extend_from_slice(buf);
+            self.process();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn image() -> Vec<u8> {
+        (0..5000u32).map(|i| (i * 7) as u8).collect()
+    }
+
+    #[test]
+    fn sends_image() {
+        let image = image();
+        let header = Header::flat(&image, 0x8000, 0x8000);
+        let mut bootloader = FakeBootloader::new();
+        let mut last_progress = 0;
+        send(&mut bootloader, &header, &image, |sent, total| {
+            assert_eq!(total, image.len());
+            last_progress = sent;
+        })
+        .unwrap();
+        assert_eq!(last_progress, image.len());
+        assert_eq!(bootloader.image, image);
+    }
+
+    #[test]
+    fn retransmits_rejected_frames() {
+        let image = image();
+        let header = Header::flat(&image, 0x8000, 0x8000);
+        let mut bootloader = FakeBootloader::new();
+        bootloader.reject_header = 2;
+        bootloader.corrupt_chunk = Some(1);
+        send(&mut bootloader, &header, &image, |_, _| {}).unwrap();
+        assert_eq!(bootloader.image, image);
+    }
+
+    #[test]
+    fn gives_up_on_rejected_header() {
+        let image = image();
+        let header = Header::flat(&image, 0x8000, 0x8000);
+        let mut bootloader = FakeBootloader::new();
+        bootloader.reject_header = MAX_RETRIES;
+        assert!(send(&mut bootloader, &header, &image, |_, _| {}).is_err());
+    }
+}
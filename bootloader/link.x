@@ -7,6 +7,11 @@ __physical_load_address = 0x8000;
    receive over UART at the __physical_load_address. */
 __relocate_address = 0x2000000;
 
+/* ELF images are received here, right after the relocated bootloader, before their segments are put
+   in place. */
+__staging_address = __relocate_address + 0x100000;
+__staging_end = __staging_address + 0x1000000;
+
 MEMORY {
     ram : ORIGIN = __physical_load_address, LENGTH = 0x100000
     relocate : ORIGIN = __relocate_address, LENGTH = 0x100000
@@ -1,7 +1,8 @@
 .section ".text.boot"
 
 .globl _start
-// Let's not use r0, r1, r2, for now, I think they hold useful values such as atags, and other stuff.
+// r0, r1 and r2 hold values given by the firmware (such as the atags), we leave them untouched so
+// that they can be handed over to the kernel.
 _start:
     // Set the stack pointer.
     mov sp, #0x8000
@@ -14,8 +15,8 @@ relocate:
     ldr r5, =__data_end
 
 1:
-    ldmia r3!, {r6, r7, r8, r9}
-    stmia r4!, {r6, r7, r8, r9}
+    ldmia r3!, {{r6, r7, r8, r9}}
+    stmia r4!, {{r6, r7, r8, r9}}
 
     // Check if we're done relocating.
     cmp r4, r5
@@ -35,38 +36,62 @@ zero_bss:
     // This break is pc-relative, so it does not use the relocated address.
     // See: https://sourceware.org/binutils/docs/as/Symbol-Names.html.
     blo 1b
-    
-    // Call into Rust.
+
+    // The GPIO driver uses atomics, and exclusive loads and stores do not work without the MMU and
+    // the data cache. We use the kernel's translation table, which identity maps the RAM and MMIO.
+enable_mmu:
+    // Translation table base address, with the flags corresponding to the type of memory the
+    // translation table is stored in. See section B4.9.3
+    ldr r3, ={TRANSLATION_TABLE}
+    mov r4, #0
+    orr r3, r3, #27
+    mcr p15, #0, r3, c2, c0, #0
+    // Set the value of `N` to 0 in the translation table base control register
+    mcr p15, #0, r4, c2, c0, #2
+    // Set all domains to manager mode
+    mvn r3, #0
+    mcr p15, #0, r3, c3, c0, #0
+
+    // Invalidate caches and TLB
+    mcr p15, #0, r4, c7, c7, #0
+    mcr p15, #0, r4, c8, c7, #0
+
+    // Enable the MMU, L1 caches and branch prediction. See section B3.4.1
+    mrc p15, #0, r3, c1, c0, #0
+    orr r3, r3, #5
+    orr r3, r3, #6144
+    mcr p15, #0, r3, c1, c0, #0
+    // data synchronization barrier
+    mcr p15, #0, r4, c7, c10, #4
+
+    // Call into Rust, with r0-r2 as arguments.
     // This uses the relocated address, and is an absolute jump.
-    ldr r3, =first_stage
+    ldr r3, ={FIRST_STAGE}
     blx r3
 
-.globl mem_barrier
+// Jump to the kernel at the address in r3, with r0-r2 as given by the firmware.
+//
+// The kernel sets up the MMU by itself, so we turn it off along with the caches, making sure that
+// everything we wrote is in memory first.
+.globl jump_to_kernel
+jump_to_kernel:
+    mov r4, #0
+    // Clean and invalidate the data cache. See section B6.6.5
+    mcr p15, #0, r4, c7, c14, #0
+    mcr p15, #0, r4, c7, c10, #4
+
+    // Disable the MMU, L1 caches and branch prediction.
+    mrc p15, #0, r5, c1, c0, #0
+    bic r5, r5, #5
+    bic r5, r5, #6144
+    mcr p15, #0, r5, c1, c0, #0
+
+    // Invalidate the instruction cache, branch predictor and TLB.
+    mcr p15, #0, r4, c7, c5, #0
+    mcr p15, #0, r4, c7, c5, #6
+    mcr p15, #0, r4, c8, c7, #0
+    mcr p15, #0, r4, c7, c10, #4
+    // Flush the prefetch buffer
+    mcr p15, #0, r4, c7, c5, #4
 
-/**
- * @fn void dmb(void)
- *
- * Executes a data memory barrier operation using the c7 (Cache Operations)
- * register of system control coprocessor CP15.
- *
- * All explicit memory accesses occurring in program order before this operation
- * will be globally observed before any memory accesses occurring in program
- * order after this operation.  This includes both read and write accesses.
- *
- * This differs from a "data synchronization barrier" in that a data
- * synchronization barrier will ensure that all previous explicit memory
- * accesses occurring in program order have fully completed before continuing
- * and that no subsequent instructions will be executed until that point, even
- * if they do not access memory.  This is unnecessary for what we need this for.
- *
- * On the BCM2835 (Raspberry Pi), this is needed before and after accessing
- * peripherals, as documented on page 7 of the "BCM2835 ARM Peripherals"
- * document.  As documented, it is only needed when switching between
- * _different_ peripherals.
- *
-* TODO: When confident rewrite this in inline asm!.
- */
-mem_barrier:
-	mov	r12, #0
-	mcr	p15, 0, r12, c7, c10, 5
-	mov 	pc, lr
+    bx r3
@@ -1,51 +1,235 @@
 #![no_std]
 #![no_main]
-use rpi::gpio::{Alternate5, Pin};
-
-core::arch::global_asm!(include_str!("boot.s"), options(raw));
-
-#[no_mangle]
-pub extern "C" fn first_stage() -> ! {
-    let mut uart = rpi::uart::MiniUart::get().unwrap();
-    uart.set_bit_mode(true);
-    uart.set_baud_rate(115200);
-    let tx_pin: Pin<14, Alternate5> = Pin::get().unwrap();
-    let rx_pin: Pin<15, Alternate5> = Pin::get().unwrap();
-    let mut rx_tx = uart.enable_transmitter(tx_pin).enable_receiver(rx_pin);
-    rx_tx.send_blocking([0xff].into_iter());
-
-    let mut binary_size_bytes = [0u8; 4];
-    rx_tx.receive_exact(&mut binary_size_bytes);
-    let mut binary_size = u32::from_le_bytes(binary_size_bytes);
-
-    let mut byte_buf = [0u8];
-    // Safety: This value is provided by the linker script.
-    unsafe extern "C" {
-        #[link_name = "__physical_load_address"]
-        static LOAD_ADDRESS: u8;
+
+use core::{arch::global_asm, convert::Infallible, ops::Range, ptr, slice};
+
+use chainload::{
+    crc32, elf::Elf, Chunk, Format, Header, ACK, CHUNK_PREFIX_LEN, CHUNK_SIZE, HELLO,
+    MAX_CHUNK_LEN, NAK,
+};
+use embassy_time_driver::now;
+use rpi::{
+    aux::{
+        self,
+        uart::{self, flow::FlowControl, BaudRate, BitMode},
+    },
+    eio::{Read, ReadReady, Write},
+    gpio::{self, state::Alternate5},
+};
+
+global_asm!(
+    include_str!("boot.s"),
+    TRANSLATION_TABLE = sym rpi::mmu::TRANSLATION_TABLE,
+    FIRST_STAGE = sym first_stage,
+);
+
+extern "C" {
+    #[link_name = "__physical_load_address"]
+    static LOAD_ADDRESS: u8;
+    #[link_name = "__relocate_address"]
+    static RELOCATE_ADDRESS: u8;
+    #[link_name = "__staging_address"]
+    static STAGING_ADDRESS: u8;
+    #[link_name = "__staging_end"]
+    static STAGING_END: u8;
+
+    /// Clean up the caches and the MMU, then jump to `entry` with `r0`-`r2` set to the provided
+    /// values.
+    fn jump_to_kernel(r0: u32, r1: u32, r2: u32, entry: *const u8) -> !;
+}
+
+/// How long we wait for the host to send a header before saying hello again, in microseconds.
+const HELLO_INTERVAL: u64 = 1_000_000;
+/// How long the line can stay quiet while we expect a frame, in microseconds.
+const TIMEOUT: u64 = 250_000;
+/// How many times in a row a chunk can be rejected before we start over.
+const MAX_RETRIES: u32 = 16;
+
+/// Called from `boot.s` with the registers given by the firmware.
+extern "C" fn first_stage(r0: u32, r1: u32, r2: u32) -> ! {
+    critical_section::with(|cs| {
+        // Safety: Nothing has used the aux peripherals yet.
+        unsafe { aux::setup(&cs) };
+    });
+
+    let rx_pin = gpio::Pin::<15, Alternate5>::get().unwrap();
+    let tx_pin = gpio::Pin::<14, Alternate5>::get().unwrap();
+    let (mut rx, mut tx) = uart::pair(
+        rx_pin,
+        tx_pin,
+        &uart::Config {
+            baud_rate: BaudRate::new(115200).unwrap(),
+            bit_mode: BitMode::EightBits,
+            flow_control: FlowControl::None,
+        },
+    )
+    .unwrap();
+
+    // We relocated ourselves out of the way, so everything up to the relocated bootloader is free.
+    let free_memory = (&raw const LOAD_ADDRESS) as usize..(&raw const RELOCATE_ADDRESS) as usize;
+    let staging = (&raw const STAGING_ADDRESS) as usize..(&raw const STAGING_END) as usize;
+    let entry_point = loop {
+        tx.write_all(&HELLO).unwrap();
+        tx.flush().unwrap();
+
+        let mut header = [0; Header::LEN];
+        if read_timeout(&mut rx, &mut header, HELLO_INTERVAL).is_none() {
+            continue;
+        }
+        let Ok(header) = Header::decode(&header) else {
+            reject(&mut rx, &mut tx);
+            continue;
+        };
+
+        let (image_start, allowed) = match header.format {
+            Format::Flat => (header.load_address as usize, free_memory.clone()),
+            Format::Elf => (staging.start, staging.clone()),
+        };
+        let image_end = image_start.saturating_add(header.size as usize);
+        let entry_in_image = (image_start..image_end).contains(&(header.entry_point as usize));
+        if image_start < allowed.start
+            || image_end > allowed.end
+            || (header.format == Format::Flat && !entry_in_image)
+        {
+            reject(&mut rx, &mut tx);
+            continue;
+        }
+        reply(&mut tx, ACK);
+
+        // Safety: The image was checked to be in free memory, or in the staging area.
+        let image =
+            unsafe { slice::from_raw_parts_mut(image_start as *mut u8, header.size as usize) };
+        if !receive_image(&mut rx, &mut tx, image) || crc32(image) != header.checksum {
+            reject(&mut rx, &mut tx);
+            continue;
+        }
+        let entry_point = match header.format {
+            Format::Flat => Some(header.entry_point),
+            Format::Elf => load_elf(image, &free_memory),
+        };
+        match entry_point {
+            Some(entry_point) => {
+                reply(&mut tx, ACK);
+                break entry_point;
+            }
+            None => reject(&mut rx, &mut tx),
+        }
+    };
+
+    // Clean up before jumping to the kernel
+    drop((rx, tx));
+
+    // Safety: We know that the kernel is a function that never returns, and we have loaded it
+    // into memory.
+    unsafe { jump_to_kernel(r0, r1, r2, entry_point as *const u8) }
+}
+
+/// Put the loadable segments of `elf` in place, returning its entry point.
+///
+/// Returns `None` if the file is invalid or if a segment is not entirely in `free_memory`, in which
+/// case nothing is written.
+fn load_elf(elf: &[u8], free_memory: &Range<usize>) -> Option<u32> {
+    let elf = Elf::parse(elf).ok()?;
+    let fits = elf.segments().all(|segment| {
+        let range = segment.memory_range();
+        free_memory.start <= range.start as usize && range.end as usize <= free_memory.end
+    });
+    if !fits {
+        return None;
     }
-    // Safety: We know that there is nothing at __physical_load_address, since we relocated to
-    // 0x2000000 (__relocate_address).
-    unsafe {
-        let mut dest_ptr: *mut u8 = (&raw const LOAD_ADDRESS) as _;
-        while binary_size > 0 {
-            rx_tx.receive_exact(&mut byte_buf);
 
-            dest_ptr.write(byte_buf[0]);
-            dest_ptr = dest_ptr.offset(1);
-            binary_size -= 1;
+    for segment in elf.segments() {
+        let dest = segment.physical_address as *mut u8;
+        // Safety: The segment was checked to be in free memory, and it does not overlap with the
+        // ELF file, which is in the staging area.
+        unsafe {
+            ptr::copy_nonoverlapping(segment.data.as_ptr(), dest, segment.data.len());
+            dest.add(segment.data.len())
+                .write_bytes(0, (segment.memory_size as usize) - segment.data.len());
         }
     }
+    Some(elf.entry_point())
+}
 
-    // Clean up before jumping to the kernel
-    drop(rx_tx);
+/// Receive all chunks of the image, returns `false` if the host does not manage to send them.
+fn receive_image<R, W>(rx: &mut R, tx: &mut W, image: &mut [u8]) -> bool
+where
+    R: Read + ReadReady,
+    W: Write<Error = Infallible>,
+{
+    let mut frame = [0; MAX_CHUNK_LEN];
+    for (index, dest) in image.chunks_mut(CHUNK_SIZE).enumerate() {
+        let index = index as u32;
+        let mut retries = 0;
+        loop {
+            match receive_chunk(rx, &mut frame) {
+                Some(chunk) if chunk.index == index && chunk.data.len() == dest.len() => {
+                    dest.copy_from_slice(chunk.data);
+                    reply(tx, ACK);
+                    break;
+                }
+                // Our acknowledgement of the previous chunk was lost, so it was sent again.
+                Some(chunk) if index.checked_sub(1) == Some(chunk.index) => reply(tx, ACK),
+                _ => {
+                    retries += 1;
+                    if retries == MAX_RETRIES {
+                        return false;
+                    }
+                    reject(rx, tx);
+                }
+            }
+        }
+    }
+    true
+}
 
-    // Safety: We know that the kernel is a function that never returns.
-    // We also have loaded it into memory at LOAD_ADDRESS.
-    unsafe {
-        let kernel: fn() -> ! = core::mem::transmute(&raw const LOAD_ADDRESS);
-        kernel();
+fn receive_chunk<'a, R: Read + ReadReady>(
+    rx: &mut R,
+    frame: &'a mut [u8; MAX_CHUNK_LEN],
+) -> Option<Chunk<'a>> {
+    let (prefix, rest) = frame.split_at_mut(CHUNK_PREFIX_LEN);
+    read_timeout(rx, prefix, TIMEOUT)?;
+    let frame_len = Chunk::frame_len(prefix.first_chunk().unwrap()).ok()?;
+    read_timeout(rx, &mut rest[..frame_len - CHUNK_PREFIX_LEN], TIMEOUT)?;
+    Chunk::decode(&frame[..frame_len]).ok()
+}
+
+/// Fill `buf`, giving up if no byte is received for `timeout` microseconds or if a byte is lost.
+fn read_timeout<R: Read + ReadReady>(rx: &mut R, buf: &mut [u8], timeout: u64) -> Option<()> {
+    let mut filled = 0;
+    let mut deadline = now() + timeout;
+    while filled < buf.len() {
+        if rx.read_ready().ok()? {
+            filled += rx.read(&mut buf[filled..]).ok()?;
+            deadline = now() + timeout;
+        } else if now() > deadline {
+            return None;
+        }
     }
+    Some(())
+}
+
+/// Wait for the line to go quiet, then ask the host to send the last frame again.
+fn reject<R, W>(rx: &mut R, tx: &mut W)
+where
+    R: Read + ReadReady,
+    W: Write<Error = Infallible>,
+{
+    let mut byte = [0];
+    let mut deadline = now() + TIMEOUT;
+    while now() < deadline {
+        if rx.read_ready().unwrap_or(true) {
+            // Errors are expected here, we are discarding whatever is on the line.
+            let _ = rx.read(&mut byte);
+            deadline = now() + TIMEOUT;
+        }
+    }
+    reply(tx, NAK);
+}
+
+fn reply<W: Write<Error = Infallible>>(tx: &mut W, byte: u8) {
+    tx.write_all(&[byte]).unwrap();
+    tx.flush().unwrap();
 }
 
 #[panic_handler]
@@ -0,0 +1,236 @@
+//! Just enough of ELF to load the kernel: 32-bit little endian ARM executables.
+
+use core::ops::Range;
+
+const MAGIC: [u8; 4] = *b"\x7fELF";
+const CLASS_32: u8 = 1;
+const DATA_LITTLE_ENDIAN: u8 = 1;
+const TYPE_EXECUTABLE: u16 = 2;
+const MACHINE_ARM: u16 = 40;
+const HEADER_LEN: usize = 0x34;
+const PROGRAM_HEADER_LEN: usize = 0x20;
+const PT_LOAD: u32 = 1;
+
+/// Errors that can occur when parsing an ELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// The file does not start with the ELF magic bytes.
+    Magic,
+    /// The file is not a 32-bit little endian ARM executable.
+    Unsupported,
+    /// A header or a segment goes past the end of the file.
+    Truncated,
+    /// A segment is larger in the file than in memory, or wraps around the address space.
+    Segment,
+}
+
+/// A parsed ELF file.
+#[derive(Debug, Clone, Copy)]
+pub struct Elf<'a> {
+    bytes: &'a [u8],
+    entry_point: u32,
+    program_headers: &'a [u8],
+}
+
+impl<'a> Elf<'a> {
+    /// Parse `bytes`, checking that all of its loadable segments are valid.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.get(..4) != Some(&MAGIC) {
+            return Err(Error::Magic);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+        if bytes[4] != CLASS_32
+            || bytes[5] != DATA_LITTLE_ENDIAN
+            || read_u16(bytes, 0x10) != TYPE_EXECUTABLE
+            || read_u16(bytes, 0x12) != MACHINE_ARM
+            || read_u16(bytes, 0x2A) as usize != PROGRAM_HEADER_LEN
+        {
+            return Err(Error::Unsupported);
+        }
+
+        let offset = read_u32(bytes, 0x1C) as usize;
+        let len = read_u16(bytes, 0x2C) as usize * PROGRAM_HEADER_LEN;
+        let program_headers = offset
+            .checked_add(len)
+            .and_then(|end| bytes.get(offset..end))
+            .ok_or(Error::Truncated)?;
+
+        let elf = Self {
+            bytes,
+            entry_point: read_u32(bytes, 0x18),
+            program_headers,
+        };
+        for header in elf.program_headers.chunks_exact(PROGRAM_HEADER_LEN) {
+            if read_u32(header, 0) == PT_LOAD {
+                elf.segment(header)?;
+            }
+        }
+        Ok(elf)
+    }
+
+    /// The address of the first instruction.
+    pub fn entry_point(&self) -> u32 {
+        self.entry_point
+    }
+
+    /// The segments that must be loaded in memory.
+    pub fn segments(&self) -> impl Iterator<Item = Segment<'a>> + '_ {
+        self.program_headers
+            .chunks_exact(PROGRAM_HEADER_LEN)
+            .filter(|header| read_u32(header, 0) == PT_LOAD)
+            // Segments were checked when parsing.
+            .filter_map(|header| self.segment(header).ok())
+    }
+
+    fn segment(&self, header: &[u8]) -> Result<Segment<'a>, Error> {
+        let offset = read_u32(header, 0x04) as usize;
+        let file_size = read_u32(header, 0x10);
+        let memory_size = read_u32(header, 0x14);
+        let physical_address = read_u32(header, 0x0C);
+        if file_size > memory_size || physical_address.checked_add(memory_size).is_none() {
+            return Err(Error::Segment);
+        }
+        let data = offset
+            .checked_add(file_size as usize)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or(Error::Truncated)?;
+        Ok(Segment {
+            physical_address,
+            virtual_address: read_u32(header, 0x08),
+            data,
+            memory_size,
+        })
+    }
+}
+
+/// A loadable segment of an ELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Segment<'a> {
+    /// Where the segment goes in memory.
+    pub physical_address: u32,
+    /// Where the segment is seen by the program, once the MMU is set up.
+    pub virtual_address: u32,
+    /// The contents of the segment. It is followed by zeros up to `memory_size`.
+    pub data: &'a [u8],
+    pub memory_size: u32,
+}
+
+impl Segment<'_> {
+    /// The physical memory occupied by the segment.
+    pub fn memory_range(&self) -> Range<u32> {
+        self.physical_address..self.physical_address + self.memory_size
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// Build an executable with the given `(physical address, data, memory size)` segments.
+    fn build(entry_point: u32, segments: &[(u32, &[u8], u32)]) -> Vec<u8> {
+        let mut elf = vec![0; HEADER_LEN];
+        elf[..4].copy_from_slice(&MAGIC);
+        elf[4] = CLASS_32;
+        elf[5] = DATA_LITTLE_ENDIAN;
+        elf[6] = 1;
+        elf[0x10..0x12].copy_from_slice(&TYPE_EXECUTABLE.to_le_bytes());
+        elf[0x12..0x14].copy_from_slice(&MACHINE_ARM.to_le_bytes());
+        elf[0x18..0x1C].copy_from_slice(&entry_point.to_le_bytes());
+        elf[0x1C..0x20].copy_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+        elf[0x2A..0x2C].copy_from_slice(&(PROGRAM_HEADER_LEN as u16).to_le_bytes());
+        elf[0x2C..0x2E].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+
+        let mut offset = HEADER_LEN + segments.len() * PROGRAM_HEADER_LEN;
+        for (address, data, memory_size) in segments {
+            for field in [
+                PT_LOAD,
+                offset as u32,
+                *address,
+                *address,
+                data.len() as u32,
+                *memory_size,
+                0b101,
+                4,
+            ] {
+                elf.extend_from_slice(&field.to_le_bytes());
+            }
+            offset += data.len();
+        }
+        for (_, data, _) in segments {
+            elf.extend_from_slice(data);
+        }
+        elf
+    }
+
+    #[test]
+    fn segments() {
+        let bytes = build(0x8000, &[(0x8000, &[1, 2, 3, 4], 4), (0x9000, &[5, 6], 16)]);
+        let elf = Elf::parse(&bytes).unwrap();
+        assert_eq!(elf.entry_point(), 0x8000);
+
+        let segments: Vec<_> = elf.segments().collect();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].data, &[1, 2, 3, 4]);
+        assert_eq!(segments[0].memory_range(), 0x8000..0x8004);
+        assert_eq!(segments[1].data, &[5, 6]);
+        assert_eq!(segments[1].memory_range(), 0x9000..0x9010);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(Elf::parse(b"not an elf file").unwrap_err(), Error::Magic);
+
+        let bytes = build(0x8000, &[(0x8000, &[1, 2, 3, 4], 2)]);
+        assert_eq!(Elf::parse(&bytes).unwrap_err(), Error::Segment);
+
+        let bytes = build(0x8000, &[(0xFFFF_FFF0, &[1, 2, 3, 4], 32)]);
+        assert_eq!(Elf::parse(&bytes).unwrap_err(), Error::Segment);
+
+        let bytes = build(0x8000, &[(0x8000, &[1, 2, 3, 4], 4)]);
+        assert_eq!(
+            Elf::parse(&bytes[..bytes.len() - 1]).unwrap_err(),
+            Error::Truncated
+        );
+
+        let mut bytes = build(0x8000, &[]);
+        bytes[0x12] = 3;
+        assert_eq!(Elf::parse(&bytes).unwrap_err(), Error::Unsupported);
+    }
+
+    /// Parse the kernel, run with `just test-kernel`.
+    #[test]
+    #[ignore = "needs the kernel built with `just build`"]
+    fn kernel() {
+        let target = Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/armv6a-none-eabihf");
+        let bytes = ["release", "debug"]
+            .iter()
+            .find_map(|profile| std::fs::read(target.join(profile).join("kernel")).ok())
+            .expect("the kernel is not built");
+
+        let elf = Elf::parse(&bytes).unwrap();
+        // The kernel is linked at the address the firmware loads it at.
+        assert_eq!(elf.entry_point(), 0x8000);
+        let mut segments = elf.segments().peekable();
+        assert!(segments.peek().is_some());
+        for segment in segments {
+            assert!(segment.physical_address >= 0x8000);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::crc::{crc32, Crc32};
+
+/// Identifies the chain loading protocol on the line.
+pub const MAGIC: [u8; 4] = *b"RPCL";
+/// The version of the protocol implemented by this crate.
+pub const VERSION: u8 = 2;
+/// Sent by the bootloader when it is ready to receive a [`Header`].
+pub const HELLO: [u8; 5] = [MAGIC[0], MAGIC[1], MAGIC[2], MAGIC[3], VERSION];
+/// Positive reply to a frame.
+pub const ACK: u8 = 0x06;
+/// Negative reply to a frame, which must be sent again.
+pub const NAK: u8 = 0x15;
+/// The maximum number of image bytes in a [`Chunk`].
+pub const CHUNK_SIZE: usize = 1024;
+
+/// Errors that can occur when decoding a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// The frame does not start with [`MAGIC`].
+    Magic,
+    /// The frame is for another version of the protocol.
+    Version(u8),
+    /// The header describes an image in an unknown format.
+    Format(u8),
+    /// The frame does not match its checksum.
+    Checksum,
+    /// The chunk claims to hold more than [`CHUNK_SIZE`] bytes.
+    ChunkLength(u16),
+    /// The frame is shorter than it claims to be.
+    Truncated,
+}
+
+/// How the bootloader should load the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Format {
+    /// Raw bytes, written at the load address of the header.
+    Flat = 0,
+    /// An ELF file, whose loadable segments are written at their physical addresses.
+    Elf = 1,
+}
+
+/// Describes the image that follows.
+///
+/// Layout: `MAGIC | VERSION | format | size | load_address | entry_point | checksum | header CRC`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub format: Format,
+    /// The size of the image, in bytes.
+    pub size: u32,
+    /// The address at which the image is written, ignored for ELF files.
+    pub load_address: u32,
+    /// The address jumped to once the image is loaded, ignored for ELF files.
+    pub entry_point: u32,
+    /// The CRC-32 of the whole image.
+    pub checksum: u32,
+}
+
+impl Header {
+    /// The length of an encoded header.
+    pub const LEN: usize = MAGIC.len() + 2 + 5 * 4;
+
+    /// Describe a flat `image`, loaded at `load_address`.
+    pub fn flat(image: &[u8], load_address: u32, entry_point: u32) -> Self {
+        Self {
+            format: Format::Flat,
+            size: image.len() as u32,
+            load_address,
+            entry_point,
+            checksum: crc32(image),
+        }
+    }
+
+    /// Describe an ELF file.
+    pub fn elf(image: &[u8]) -> Self {
+        Self {
+            format: Format::Elf,
+            size: image.len() as u32,
+            load_address: 0,
+            entry_point: 0,
+            checksum: crc32(image),
+        }
+    }
+
+    /// The number of chunks needed to send the image.
+    pub fn chunk_count(&self) -> u32 {
+        self.size.div_ceil(CHUNK_SIZE as u32)
+    }
+
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = self.format as u8;
+        bytes[6..10].copy_from_slice(&self.size.to_le_bytes());
+        bytes[10..14].copy_from_slice(&self.load_address.to_le_bytes());
+        bytes[14..18].copy_from_slice(&self.entry_point.to_le_bytes());
+        bytes[18..22].copy_from_slice(&self.checksum.to_le_bytes());
+        let crc = crc32(&bytes[..22]);
+        bytes[22..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8; Self::LEN]) -> Result<Self, Error> {
+        if bytes[..4] != MAGIC {
+            return Err(Error::Magic);
+        }
+        if bytes[4] != VERSION {
+            return Err(Error::Version(bytes[4]));
+        }
+        if crc32(&bytes[..22]) != read_u32(&bytes[22..]) {
+            return Err(Error::Checksum);
+        }
+        let format = match bytes[5] {
+            0 => Format::Flat,
+            1 => Format::Elf,
+            format => return Err(Error::Format(format)),
+        };
+        Ok(Self {
+            format,
+            size: read_u32(&bytes[6..]),
+            load_address: read_u32(&bytes[10..]),
+            entry_point: read_u32(&bytes[14..]),
+            checksum: read_u32(&bytes[18..]),
+        })
+    }
+}
+
+/// The length of the index and length fields that start a [`Chunk`].
+pub const CHUNK_PREFIX_LEN: usize = 4 + 2;
+/// The length of the largest encoded [`Chunk`].
+pub const MAX_CHUNK_LEN: usize = CHUNK_PREFIX_LEN + CHUNK_SIZE + 4;
+
+/// A piece of the image.
+///
+/// Layout: `index | length | data | CRC`, where the CRC covers everything before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chunk<'a> {
+    /// The position of the chunk in the image, the chunk starts at `index * CHUNK_SIZE`.
+    pub index: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    /// Split `image` into the chunks that are sent for it.
+    pub fn split(image: &'a [u8]) -> impl ExactSizeIterator<Item = Chunk<'a>> {
+        image
+            .chunks(CHUNK_SIZE)
+            .enumerate()
+            .map(|(index, data)| Chunk {
+                index: index as u32,
+                data,
+            })
+    }
+
+    /// Encode the chunk into `buf`, returning the encoded frame.
+    ///
+    /// Panics if the chunk holds more than [`CHUNK_SIZE`] bytes.
+    pub fn encode<'b>(&self, buf: &'b mut [u8; MAX_CHUNK_LEN]) -> &'b [u8] {
+        assert!(self.data.len() <= CHUNK_SIZE, "chunk is too large");
+        let data_end = CHUNK_PREFIX_LEN + self.data.len();
+        buf[..4].copy_from_slice(&self.index.to_le_bytes());
+        buf[4..6].copy_from_slice(&(self.data.len() as u16).to_le_bytes());
+        buf[6..data_end].copy_from_slice(self.data);
+        let crc = crc32(&buf[..data_end]);
+        buf[data_end..data_end + 4].copy_from_slice(&crc.to_le_bytes());
+        &buf[..data_end + 4]
+    }
+
+    /// The length of the whole frame, given its prefix.
+    pub fn frame_len(prefix: &[u8; CHUNK_PREFIX_LEN]) -> Result<usize, Error> {
+        let len = u16::from_le_bytes([prefix[4], prefix[5]]);
+        if len as usize > CHUNK_SIZE {
+            return Err(Error::ChunkLength(len));
+        }
+        Ok(CHUNK_PREFIX_LEN + len as usize + 4)
+    }
+
+    pub fn decode(frame: &'a [u8]) -> Result<Self, Error> {
+        let prefix = frame.first_chunk().ok_or(Error::Truncated)?;
+        let frame_len = Self::frame_len(prefix)?;
+        if frame.len() < frame_len {
+            return Err(Error::Truncated);
+        }
+        let (content, crc) = frame[..frame_len].split_at(frame_len - 4);
+        let mut checksum = Crc32::new();
+        checksum.update(content);
+        if checksum.finish() != read_u32(crc) {
+            return Err(Error::Checksum);
+        }
+        Ok(Self {
+            index: read_u32(content),
+            data: &content[CHUNK_PREFIX_LEN..],
+        })
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trip() {
+        let header = Header::flat(&[1, 2, 3, 4, 5], 0x8000, 0x8004);
+        assert_eq!(header.size, 5);
+        assert_eq!(Header::decode(&header.encode()), Ok(header));
+
+        let header = Header::elf(&[1, 2, 3]);
+        assert_eq!(Header::decode(&header.encode()), Ok(header));
+    }
+
+    #[test]
+    fn header_corruption() {
+        let header = Header::flat(&[0; 3000], 0x8000, 0x8000);
+        assert_eq!(header.chunk_count(), 3);
+
+        let mut bytes = header.encode();
+        bytes[10] ^= 0x40;
+        assert_eq!(Header::decode(&bytes), Err(Error::Checksum));
+
+        let mut bytes = header.encode();
+        bytes[0] = b'X';
+        assert_eq!(Header::decode(&bytes), Err(Error::Magic));
+
+        let mut bytes = header.encode();
+        bytes[4] = VERSION + 1;
+        assert_eq!(Header::decode(&bytes), Err(Error::Version(VERSION + 1)));
+
+        let mut bytes = header.encode();
+        bytes[5] = 7;
+        let crc = crc32(&bytes[..22]);
+        bytes[22..].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(Header::decode(&bytes), Err(Error::Format(7)));
+    }
+
+    #[test]
+    fn chunk_round_trip() {
+        let image: Vec<u8> = (0..2500u32).map(|i| i as u8).collect();
+        let mut buf = [0; MAX_CHUNK_LEN];
+        let mut received = Vec::new();
+        for chunk in Chunk::split(&image) {
+            let frame = chunk.encode(&mut buf);
+            let prefix = frame.first_chunk().unwrap();
+            assert_eq!(Chunk::frame_len(prefix), Ok(frame.len()));
+            let decoded = Chunk::decode(frame).unwrap();
+            assert_eq!(decoded, chunk);
+            received.extend_from_slice(decoded.data);
+        }
+        assert_eq!(received, image);
+        assert_eq!(Chunk::split(&image).len(), 3);
+    }
+
+    #[test]
+    fn chunk_corruption() {
+        let mut buf = [0; MAX_CHUNK_LEN];
+        let chunk = Chunk {
+            index: 7,
+            data: b"hello",
+        };
+        let len = chunk.encode(&mut buf).len();
+
+        let mut corrupted = buf;
+        corrupted[8] ^= 1;
+        assert_eq!(Chunk::decode(&corrupted[..len]), Err(Error::Checksum));
+        assert_eq!(Chunk::decode(&buf[..len - 1]), Err(Error::Truncated));
+
+        let mut too_long = buf;
+        too_long[4..6].copy_from_slice(&(CHUNK_SIZE as u16 + 1).to_le_bytes());
+        assert_eq!(
+            Chunk::decode(&too_long),
+            Err(Error::ChunkLength(CHUNK_SIZE as u16 + 1))
+        );
+    }
+}
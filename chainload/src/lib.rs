@@ -0,0 +1,32 @@
+//! The chain loading protocol spoken between the bootloader and `bootcom`.
+//!
+//! This crate is `no_std` so that the bootloader can use it, and has no hardware dependencies so
+//! that it can be tested on the host.
+//!
+//! # Protocol
+//!
+//! All integers are little endian, and all checksums are CRC-32 (the one used by zlib and
+//! Ethernet).
+//!
+//! 1. The bootloader sends [`HELLO`], which is the [`MAGIC`] bytes followed by the protocol
+//!    [`VERSION`]. It is sent again every second until a header is received.
+//! 2. The host sends a [`Header`] frame, describing the [`Format`] of the image, where it goes
+//!    and where to jump to.
+//!    The bootloader replies [`ACK`] if it accepts it, [`NAK`] otherwise.
+//! 3. The host sends the image in [`Chunk`] frames of at most [`CHUNK_SIZE`] bytes. The
+//!    bootloader replies [`ACK`] to each valid chunk and [`NAK`] to corrupted or incomplete ones,
+//!    which the host must then send again.
+//! 4. Once the last chunk is acknowledged, the bootloader checks the CRC of the whole image (and
+//!    loads the segments of ELF files). It replies [`ACK`] and jumps to the entry point, or
+//!    [`NAK`] and starts over from step 1.
+//!
+//! Before replying [`NAK`], the bootloader waits for the line to go quiet, so that the host is
+//! never in the middle of a frame when it receives the reply.
+#![cfg_attr(not(test), no_std)]
+
+mod crc;
+pub mod elf;
+mod frame;
+
+pub use crc::{crc32, Crc32};
+pub use frame::*;
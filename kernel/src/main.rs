@@ -8,12 +8,12 @@ use core::{
 use rpi::{
     aux::{
         self,
-        uart::{BaudRate, BitMode},
+        uart::{flow::FlowControl, BaudRate, BitMode},
     },
     data_memory_barrier,
     eio::Write,
     gpio::{self},
-    main,
+    hal, main,
 };
 
 #[main]
@@ -22,45 +22,38 @@ fn main() -> ! {
         aux::uart::Writer::get_unchecked(
             gpio::Pin::<14, _>::get().unwrap(),
             &aux::uart::Config {
-                baud_rate: BaudRate::new(115200),
+                baud_rate: BaudRate::new(115200).unwrap(),
                 bit_mode: BitMode::EightBits,
+                flow_control: FlowControl::None,
             },
         )
     };
 
-    const AUX_ENABLES: *mut u32 = 0x20215004 as _;
-    const SPI_CNTL0: *mut u32 = 0x20215080 as _;
-    const SPI_CNTL1: *mut u32 = 0x20215084 as _;
-    const SPI_STAT: *mut u32 = 0x20215088 as _;
-    const SPI_IO: *mut u32 = 0x202150A0 as _;
-    const SPI_TXHOLD: *mut u32 = 0x202150B0 as _;
-
-    // Enable the SPI peripheral
-    unsafe { write_volatile(AUX_ENABLES, read_volatile(AUX_ENABLES) | 0b10) };
-    let speed = 4999;
-    let chip_select = 1;
-    let cntl0 = (speed << 20) | (chip_select << 17) | (0b11 << 14) | (1 << 11) | 0b100;
-    unsafe { write_volatile(SPI_CNTL0, cntl0) };
-    unsafe { SPI_IO.write_volatile(8 << 24) };
-    let status = unsafe { SPI_STAT.read_volatile() };
-    tx.write_fmt(format_args!("SPI_STAT: {:#010X}\n", status))
-        .unwrap();
-    tx.write_fmt(format_args!("tx level: {}\n", status >> 28))
+    let config = aux::spi::Config::builder()
+        .frequency(25_000)
+        .build()
         .unwrap();
+    tx.write_fmt(format_args!(
+        "SPI frequency: {} Hz\n",
+        config.speed.frequency()
+    ))
+    .unwrap();
 
-    let cntl0 = unsafe { SPI_CNTL0.read_volatile() };
-    tx.write_fmt(format_args!("SPI_CNTL0: {:#010X}\n", cntl0))
-        .unwrap();
-    unsafe { SPI_CNTL0.write_volatile(cntl0 | 1 << 9) };
-    let cntl0 = unsafe { SPI_CNTL0.read_volatile() };
-    tx.write_fmt(format_args!("SPI_CNTL0: {:#010X}\n", cntl0))
-        .unwrap();
-    unsafe { SPI_CNTL0.write_volatile(cntl0 | !(1 << 9)) };
+    // Enable the SPI peripheral
+    let mut spi: aux::spi::Spi1 = aux::spi::Spi1::get(
+        gpio::Pin::<19, _>::get().unwrap(),
+        gpio::Pin::<20, _>::get().unwrap(),
+        gpio::Pin::<21, _>::get().unwrap(),
+        &config,
+    )
+    .unwrap();
+    hal::spi::SpiBus::write(&mut spi, &[0u8]).unwrap();
+    hal::spi::SpiBus::flush(&mut spi).unwrap();
+    spi.clear_fifos();
 
-    let status = unsafe { SPI_STAT.read_volatile() };
-    tx.write_fmt(format_args!("SPI_STAT: {:#010X}\n", status))
-        .unwrap();
-    tx.write_fmt(format_args!("rx level: {}\n", status >> 20))
+    let mut read = [0u8];
+    hal::spi::SpiBus::transfer(&mut spi, &mut read, &[0xA5]).unwrap();
+    tx.write_fmt(format_args!("SPI read: {:#04X}\n", read[0]))
         .unwrap();
 
     loop {}
@@ -72,8 +65,9 @@ fn main() -> ! {
 //         gpio::Pin::<15, _>::get().unwrap(),
 //         gpio::Pin::<14, _>::get().unwrap(),
 //         &aux::uart::Config {
-//             baud_rate: aux::uart::BaudRate::new(115200),
+//             baud_rate: aux::uart::BaudRate::new(115200).unwrap(),
 //             bit_mode: aux::uart::BitMode::EightBits,
+//             flow_control: aux::uart::flow::FlowControl::None,
 //         },
 //     ).unwrap();
 //
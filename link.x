@@ -3,7 +3,10 @@ __physical_load_address = 0x8000;
 ENTRY(__physical_load_address)
 SECTIONS
 {
-    /* Starts at load address. */
+    /*
+        Starts at load address. Below it are the exception vectors, and the ATAGs or device tree
+        that the firmware hands to the kernel, which must not be overwritten.
+    */
     . = __physical_load_address;
     .text :
     {
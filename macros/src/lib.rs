@@ -1,11 +1,18 @@
 use proc_macro::TokenStream as TokenStream1;
 use proc_macro2::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, Error, ItemFn, ReturnType, Signature, Type};
+use quote::{quote, quote_spanned};
+use syn::{
+    parse_macro_input, spanned::Spanned, Error, FnArg, ItemFn, PatType, ReturnType, Signature, Type,
+};
 
 /// The `#[main]` attribute macro, which is used to mark the entry point of the program.
 ///
-/// This macro must be attached to a function that takes no arguments, and never returns: () -> !.
+/// This macro must be attached to either:
+/// - A function that never returns, and that either takes no arguments, or takes the boot
+///   information: `() -> !` or `(&'static BootInfo) -> !`.
+/// - An async function that takes a `Spawner`: `async (Spawner)`. It is spawned as a task on a
+///   static `rpi::executor::Executor`, which then runs forever. The `embassy-executor` crate must
+///   be a dependency of the crate using the macro.
 #[proc_macro_attribute]
 pub fn main(args: TokenStream1, input: TokenStream1) -> TokenStream1 {
     if !args.is_empty() {
@@ -41,12 +48,6 @@ fn modify_fn(
         block,
     }: ItemFn,
 ) -> Result<TokenStream, Error> {
-    if asyncness.is_some() {
-        return Err(Error::new_spanned(
-            asyncness,
-            "main function cannot be async",
-        ));
-    }
     if unsafety.is_some() {
         return Err(Error::new_spanned(
             unsafety,
@@ -65,18 +66,71 @@ fn modify_fn(
             "main function cannot have generic parameters",
         ));
     }
-    if !inputs.is_empty() {
-        return Err(Error::new_spanned(
-            inputs,
-            "main function cannot have parameters",
-        ));
-    }
     if variadic.is_some() {
         return Err(Error::new_spanned(
             variadic,
             "main function cannot be variadic",
         ));
     }
+    if let Some(receiver @ FnArg::Receiver(_)) = inputs.first() {
+        return Err(Error::new_spanned(
+            receiver,
+            "main function cannot take `self`",
+        ));
+    }
+
+    if asyncness.is_some() {
+        if inputs.len() != 1 {
+            let message = "async main function must take exactly one parameter, the `Spawner`";
+            return Err(if inputs.is_empty() {
+                Error::new_spanned(ident, message)
+            } else {
+                Error::new_spanned(inputs, message)
+            });
+        }
+        if let ReturnType::Type(_, return_type) = &output {
+            if !matches!(return_type.as_ref(), Type::Tuple(tuple) if tuple.elems.is_empty()) {
+                return Err(Error::new_spanned(
+                    output,
+                    "async main function cannot return a value",
+                ));
+            }
+        }
+
+        // The function becomes the first task, spawned on an executor that lives in a static.
+        return Ok(quote! {
+            #(#attrs)*
+            #[::embassy_executor::task]
+            #vis #asyncness #fn_token #ident (#inputs) #block
+
+            const _: () = {
+                #[unsafe(export_name = "_main")]
+                fn __rpi_main(_: &'static ::rpi::boot_info::BootInfo) -> ! {
+                    static mut EXECUTOR: ::core::mem::MaybeUninit<::rpi::executor::Executor> =
+                        ::core::mem::MaybeUninit::uninit();
+                    // Safety: `_main` is called once by the runtime, so this is the only
+                    // reference to the executor.
+                    let executor = unsafe {
+                        (*&raw mut EXECUTOR).write(::rpi::executor::Executor::new())
+                    };
+                    executor.run(|spawner| spawner.must_spawn(#ident(spawner)))
+                }
+            };
+        });
+    }
+
+    if inputs.len() > 1 {
+        return Err(Error::new_spanned(
+            inputs,
+            "main function takes at most one parameter, the boot information",
+        ));
+    }
+    let (param, arg) = match inputs.first() {
+        Some(FnArg::Typed(PatType { ty, .. })) => {
+            (quote!(boot_info), quote_spanned!(ty.span()=> boot_info))
+        }
+        _ => (quote!(_), quote!()),
+    };
     let ReturnType::Type(rt_token, return_type) = output else {
         return Err(Error::new_spanned(
             output,
@@ -90,11 +144,18 @@ fn modify_fn(
         ));
     };
     let output = ReturnType::Type(rt_token, return_type);
+    // The runtime always calls `_main` with a reference to the boot information, so the exported
+    // function takes it even when the user's function does not. A parameter of another type is a
+    // type error when it is passed on.
     Ok(quote! {
         #(#attrs)*
-        #[unsafe(export_name = "_main")]
-        #vis #constness #fn_token #ident () #output {
-            #block
-        }
+        #vis #constness #fn_token #ident (#inputs) #output #block
+
+        const _: () = {
+            #[unsafe(export_name = "_main")]
+            fn __rpi_main(#param: &'static ::rpi::boot_info::BootInfo) -> ! {
+                #ident(#arg)
+            }
+        };
     })
 }
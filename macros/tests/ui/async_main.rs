@@ -0,0 +1,33 @@
+use embassy_executor::Spawner;
+use macros::main;
+
+// Stands in for the `rpi` crate, which only builds for the Raspberry Pi.
+extern crate self as rpi;
+pub mod boot_info {
+    pub struct BootInfo;
+}
+pub mod executor {
+    use embassy_executor::Spawner;
+
+    pub struct Executor;
+
+    impl Executor {
+        pub fn new() -> Self {
+            Executor
+        }
+
+        pub fn run(&'static mut self, _init: impl FnOnce(Spawner)) -> ! {
+            todo!()
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+fn __pender(_context: *mut ()) {}
+
+#[main]
+async fn kernel_main(spawner: Spawner) {
+    let _ = spawner;
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use macros::main;
+
+#[main]
+async fn kernel_main(_spawner: embassy_executor::Spawner) -> ! {
+    todo!()
+}
+
+fn main() {}
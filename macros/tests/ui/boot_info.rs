@@ -0,0 +1,16 @@
+use macros::main;
+
+// Stands in for the `rpi` crate, which only builds for the Raspberry Pi.
+extern crate self as rpi;
+pub mod boot_info {
+    pub struct BootInfo {
+        pub memory_size: Option<u32>,
+    }
+}
+
+#[main]
+fn kernel_main(boot_info: &'static rpi::boot_info::BootInfo) -> ! {
+    todo!("{:?}", boot_info.memory_size)
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use macros::main;
+
+// Stands in for the `rpi` crate, which only builds for the Raspberry Pi.
+extern crate self as rpi;
+pub mod boot_info {
+    pub struct BootInfo;
+}
+
+#[main]
+fn kernel_main(memory_size: u64) -> ! {
+    todo!("{}", memory_size)
+}
+
+fn main() {}
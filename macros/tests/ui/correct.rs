@@ -1,5 +1,11 @@
 use macros::main;
 
+// Stands in for the `rpi` crate, which only builds for the Raspberry Pi.
+extern crate self as rpi;
+pub mod boot_info {
+    pub struct BootInfo;
+}
+
 #[main]
 pub fn kernel_main() -> ! {
     let a = 5;
@@ -1,4 +1,6 @@
 #[macros::main]
-pub fn kernel_main(a: u32) {}
+pub fn kernel_main(a: u32, b: u32) -> ! {
+    loop {}
+}
 
 pub fn main() {}
@@ -2,7 +2,10 @@ use core::ptr::{read_volatile, write_volatile};
 
 use critical_section::CriticalSection;
 
-use crate::data_memory_barrier;
+use crate::{
+    data_memory_barrier,
+    interrupt::{self, Source},
+};
 
 pub mod spi;
 pub mod uart;
@@ -14,18 +17,43 @@ pub const AUX_INTERRUPT_STATUS: *mut u32 = 0x20215000 as _;
 /// BCM2835 ARM Peripherals, page 9
 pub const AUX_ENABLES: *mut u32 = 0x20215004 as _;
 
-/// Safety: Must be called before main.
-pub(crate) unsafe fn setup(cs: &CriticalSection) {
+/// Enable the Mini UART and put it in a known state.
+///
+/// This is done by the runtime before `main` when the `rt` feature is enabled, code that brings
+/// its own entry point (such as the bootloader) must call it before using the Mini UART.
+///
+/// # Safety
+///
+/// Must be called once, before any of the aux peripherals are used.
+pub unsafe fn setup(cs: &CriticalSection) {
     data_memory_barrier();
     // Safety: Address valid, data memory barrier used, we have a cs lock.
     unsafe {
         let enable_state = read_volatile(AUX_ENABLES);
         write_volatile(AUX_ENABLES, enable_state | 1);
     }
-    // Safety: call before main ensured by the caller.
+    // Safety: call before any use of the peripheral ensured by the caller.
     unsafe { uart::setup(cs) };
 }
 
+/// Bind the handler of the aux interrupts, when one of the peripherals is acquired.
+fn register_interrupt() {
+    interrupt::register(Source::AUX, interrupt_handler);
+}
+
+/// Unbind the handler of the aux interrupts, once none of the peripherals is in use.
+fn release_interrupt() {
+    data_memory_barrier();
+    critical_section::with(|_| {
+        // Safety: Addresses valid, data memory barrier used. The Mini UART stays enabled in
+        // `AUX_ENABLES`, so its receiver and transmitter enable bits tell whether it is in use.
+        let in_use = unsafe { read_volatile(AUX_ENABLES) & !1 != 0 } || uart::in_use();
+        if !in_use {
+            interrupt::unregister(Source::AUX);
+        }
+    });
+}
+
 pub(crate) fn interrupt_handler() {
     data_memory_barrier();
     // Safety: Address is valid, data memory barrier used.
@@ -34,6 +62,9 @@ pub(crate) fn interrupt_handler() {
         uart::interrupt_handler();
     }
     if aux_irq & 0b10 != 0 {
-        spi::interrupt_handler();
+        spi::interrupt_handler::<spi::instance::One>();
+    }
+    if aux_irq & 0b100 != 0 {
+        spi::interrupt_handler::<spi::instance::Two>();
     }
 }
@@ -1,6 +1,7 @@
 use core::{
     convert::Infallible,
     future::Future,
+    marker::PhantomData,
     pin::Pin,
     slice,
     task::{Context, Poll},
@@ -22,16 +23,23 @@ use core::{
 use crate::{
     data_memory_barrier, data_synchronization_barrier,
     gpio::{self, state::Alternate4},
-    hal, hal_async, hal_nb, set_waker, wake, WakerCell, WAKER_CELL_INIT,
+    hal, hal_async, hal_nb, set_waker, speed, wake,
 };
 
 use super::AUX_ENABLES;
 
+pub mod device;
+pub mod instance;
 pub mod mode;
 mod registers;
-use registers::{CONTROL0, CONTROL1, IO, SPI1, STATUS, TXHOLD};
+pub use crate::speed::SpeedError;
+pub use device::{CsPin, Device};
+use instance::Instance;
+use mode::{Fixed, Mode, Variable};
+use registers::{CONTROL0, CONTROL1, IO, STATUS, TXHOLD};
 
-static SPI_WAKER: WakerCell = WAKER_CELL_INIT;
+/// The number of entries in each FIFO.
+const FIFO_DEPTH: usize = 4;
 
 // Miscellaneous thoughts:
 // - We only support variable mode for CS, because supporting fixed mode with arbitrary byte counts
@@ -45,43 +53,81 @@ static SPI_WAKER: WakerCell = WAKER_CELL_INIT;
 // - Clear fifos
 // - Invert Clock
 
-/// Aux SPI peripheral
+/// Aux SPI peripheral, either [`Spi1`] or [`Spi2`].
+///
+/// Both peripherals are identical, apart from their pins.
 ///
 /// # Implmenetation notes
 /// - On `read` transactions, we write what is initially present in the buffer.
-/// - When writing in `Fixed<N>` mode, if the number of bits per burst does not divide the number
-///     of bits provided, padding zeros are added to the last burst. For example, the mode
-///     `Fixed<15>` is chosen, and we `write(&[0x12, 0x34, 0x56, 0x78])`, then burst 1 will send
-///     15 bits, burst 2 as well, and burst 3 will send the two last bytes, along with 13 zeros.
-pub struct Spi1 {
-    _miso: gpio::Pin<19, Alternate4>,
-    _mosi: gpio::Pin<20, Alternate4>,
-    _sclk: gpio::Pin<21, Alternate4>,
+/// - In [`Variable`] mode, the bus works on bytes, sent in bursts of up to 3 bytes.
+/// - In [`Fixed<N>`] mode, the bus works on `u16` words, each sent as one burst of its `N` low
+///   bits.
+///
+/// The mode is not inferred from the default, so it must be named when getting the peripheral,
+/// e.g. `let spi: Spi1 = Spi1::get(..)`.
+pub struct Spi<I: Instance, M: Mode = Variable> {
+    _pins: I::Pins,
+    _mode: PhantomData<M>,
 }
 
-impl Spi1 {
+/// The first aux SPI, on GPIO 19 (MISO), 20 (MOSI) and 21 (SCLK).
+pub type Spi1<M = Variable> = Spi<instance::One, M>;
+/// The second aux SPI, on GPIO 40 (MISO), 41 (MOSI) and 42 (SCLK).
+pub type Spi2<M = Variable> = Spi<instance::Two, M>;
+
+impl<M: Mode> Spi1<M> {
     pub fn get(
         miso: gpio::Pin<19, Alternate4>,
         mosi: gpio::Pin<20, Alternate4>,
         sclk: gpio::Pin<21, Alternate4>,
         config: &Config,
     ) -> Option<Self> {
+        Self::enable(config)?;
+        Some(Spi {
+            _pins: (miso, mosi, sclk),
+            _mode: PhantomData,
+        })
+    }
+}
+
+impl<M: Mode> Spi2<M> {
+    pub fn get(
+        miso: gpio::Pin<40, Alternate4>,
+        mosi: gpio::Pin<41, Alternate4>,
+        sclk: gpio::Pin<42, Alternate4>,
+        config: &Config,
+    ) -> Option<Self> {
+        Self::enable(config)?;
+        Some(Spi {
+            _pins: (miso, mosi, sclk),
+            _mode: PhantomData,
+        })
+    }
+}
+
+impl<I: Instance, M: Mode> Spi<I, M> {
+    /// Enable the peripheral and apply `config`.
+    ///
+    /// Returns `None` if the peripheral is already in use.
+    fn enable(config: &Config) -> Option<()> {
         data_memory_barrier();
         critical_section::with(|_| {
             // Safety: Register is valid, data barrier used.
             let aux_enables = unsafe { AUX_ENABLES.read_volatile() };
-            if aux_enables & (0b1 << 1) != 0 {
+            if aux_enables & I::AUX_BIT != 0 {
                 return None;
             }
             // Safety: Same as above.
-            unsafe { AUX_ENABLES.write_volatile(aux_enables | (0b1 << 1)) };
+            unsafe { AUX_ENABLES.write_volatile(aux_enables | I::AUX_BIT) };
             Some(())
         })?;
+        super::register_interrupt();
 
         // We have exclusive access to the peripheral, so we can do whatever with the registers.
-        let cntl0 = config.speed.0 << 20
+        let cntl0 = (config.speed.divider() / 2 - 1) << 20
+            | 0b111 << 17 // Native chip selects deasserted, see `device`
             | (config.post_input as u32) << 16
-            | 1 << 14 // Variable mode
+            | M::CONFIG_MASK
             | (config.data_out_hold as u32) << 12
             | 1 << 11 // Enable
             | (config.in_rising as u32) << 10
@@ -89,19 +135,15 @@ impl Spi1 {
             | ((config.polarity == hal::spi::Polarity::IdleHigh) as u32) << 7
             | (config.out_most_significant_first as u32) << 6;
         // Safety: As above.
-        unsafe { SPI1.add(CONTROL0).write_volatile(cntl0) };
+        unsafe { I::BASE.add(CONTROL0).write_volatile(cntl0) };
 
         let cntl1 = (config.extra_cs_high_time.0 as u32) << 8
             | (config.in_most_significant_first as u32) << 1
             | config.keep_input as u32;
         // Safety: As above.
-        unsafe { SPI1.add(CONTROL1).write_volatile(cntl1) };
+        unsafe { I::BASE.add(CONTROL1).write_volatile(cntl1) };
 
-        Some(Spi1 {
-            _miso: miso,
-            _mosi: mosi,
-            _sclk: sclk,
-        })
+        Some(())
     }
 
     /// Is the SPI peripheral busy?
@@ -111,28 +153,38 @@ impl Spi1 {
     /// A data memory barrier must have been used
     unsafe fn busy(&self) -> bool {
         // Safety: Address is valid, data memory barrier ensured by the caller.
-        unsafe { SPI1.add(STATUS).read_volatile() >> 6 & 1 == 1 }
+        unsafe { I::BASE.add(STATUS).read_volatile() >> 6 & 1 == 1 }
     }
 
     pub fn clear_fifos(&mut self) {
         data_memory_barrier();
         // Safety: Adress valid, data barrier used, and we have exclusive access.
-        let reg = unsafe { SPI1.add(CONTROL0).read() };
+        let reg = unsafe { I::BASE.add(CONTROL0).read() };
         // Safety: As above.
-        unsafe { SPI1.add(CONTROL0).write(reg | 1 << 9) };
+        unsafe { I::BASE.add(CONTROL0).write(reg | 1 << 9) };
         // we use a data memory barrier because I don't think simply going on and off is enough, we
         // probably have to wait some time.
         data_synchronization_barrier();
         // Safety: As above.
-        unsafe { SPI1.add(CONTROL0).write(reg) };
+        unsafe { I::BASE.add(CONTROL0).write(reg) };
+    }
+
+    /// Wait until the peripheral is done, and clear the FIFOs.
+    fn wait_idle(&mut self) {
+        data_memory_barrier();
+        // Safety: data barrier used.
+        while unsafe { self.busy() } {
+            core::hint::spin_loop();
+        }
+        self.clear_fifos();
     }
 }
 
-impl hal::spi::ErrorType for Spi1 {
+impl<I: Instance, M: Mode> hal::spi::ErrorType for Spi<I, M> {
     type Error = Infallible;
 }
 
-impl hal::spi::SpiBus for Spi1 {
+impl<I: Instance> hal::spi::SpiBus for Spi<I> {
     fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
         self.transfer_in_place(words)
     }
@@ -141,18 +193,18 @@ impl hal::spi::SpiBus for Spi1 {
         data_memory_barrier();
 
         // Safety: Address valid, data memory barrier used.
-        let ms_bit_first = unsafe { SPI1.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
+        let ms_bit_first = unsafe { I::BASE.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
         for (index, chunk) in words.chunks(3).enumerate() {
             let entry = to_entry(chunk, ms_bit_first);
 
             // Safety: As above.
-            while unsafe { SPI1.add(STATUS).read_volatile() >> 10 & 1 } == 1 {}
+            while unsafe { I::BASE.add(STATUS).read_volatile() >> 10 & 1 } == 1 {}
             if (index + 1) * 3 >= words.len() {
                 // Safety: As above.
-                unsafe { SPI1.add(IO).write_volatile(entry) };
+                unsafe { I::BASE.add(IO).write_volatile(entry) };
             } else {
                 // Safety: As above.
-                unsafe { SPI1.add(TXHOLD).write_volatile(entry) };
+                unsafe { I::BASE.add(TXHOLD).write_volatile(entry) };
             }
         }
 
@@ -176,25 +228,25 @@ impl hal::spi::SpiBus for Spi1 {
         self.flush()?;
 
         // Safety: Address valid, data memory barrier used.
-        let out_ms_bit_first = unsafe { SPI1.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
+        let out_ms_bit_first = unsafe { I::BASE.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
         // Safety: As above.
-        let in_ms_bit_first = unsafe { SPI1.add(CONTROL1).read_volatile() } & 1 != 0;
+        let in_ms_bit_first = unsafe { I::BASE.add(CONTROL1).read_volatile() } & 1 != 0;
         let words_len = words.len();
         for (index, chunk) in words.chunks_mut(3).enumerate() {
             let entry = to_entry(chunk, out_ms_bit_first);
 
             if (index + 1) * 3 >= words_len {
                 // Safety: As above.
-                unsafe { SPI1.add(IO).write_volatile(entry) };
+                unsafe { I::BASE.add(IO).write_volatile(entry) };
             } else {
                 // Safety: As above.
-                unsafe { SPI1.add(TXHOLD).write_volatile(entry) };
+                unsafe { I::BASE.add(TXHOLD).write_volatile(entry) };
             }
 
             // Safety: As above.
-            while unsafe { SPI1.add(STATUS).read_volatile() >> 7 & 1 } == 1 {}
+            while unsafe { I::BASE.add(STATUS).read_volatile() >> 7 & 1 } == 1 {}
             // Safety: As above.
-            let entry = unsafe { SPI1.add(IO).read_volatile() };
+            let entry = unsafe { I::BASE.add(IO).read_volatile() };
             from_entry(chunk, entry, in_ms_bit_first);
         }
 
@@ -202,17 +254,12 @@ impl hal::spi::SpiBus for Spi1 {
     }
 
     fn flush(&mut self) -> Result<(), Self::Error> {
-        data_memory_barrier();
-        // Safety: data barrier used.
-        while unsafe { self.busy() } {
-            core::hint::spin_loop();
-        }
-        self.clear_fifos();
+        self.wait_idle();
         Ok(())
     }
 }
 
-impl hal_async::spi::SpiBus for Spi1 {
+impl<I: Instance> hal_async::spi::SpiBus for Spi<I> {
     fn read(&mut self, words: &mut [u8]) -> impl Future<Output = Result<(), Self::Error>> {
         <Self as hal_async::spi::SpiBus>::transfer_in_place(self, words)
     }
@@ -256,21 +303,21 @@ impl hal_async::spi::SpiBus for Spi1 {
     }
 }
 
-pub struct WriteFut<'a, 'b> {
-    spi: &'a mut Spi1,
+pub struct WriteFut<'a, 'b, I: Instance> {
+    spi: &'a mut Spi<I>,
     chunks: slice::Chunks<'b, u8>,
 }
 
-impl Future for WriteFut<'_, '_> {
+impl<I: Instance> Future for WriteFut<'_, '_, I> {
     type Output = Result<(), Infallible>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         data_memory_barrier();
 
         // Safety: Address valid, data memory barrier used.
-        let ms_bit_first = unsafe { SPI1.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
+        let ms_bit_first = unsafe { I::BASE.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
 
-        while unsafe { SPI1.add(STATUS).read_volatile() >> 10 & 1 } == 0 {
+        while unsafe { I::BASE.add(STATUS).read_volatile() >> 10 & 1 } == 0 {
             let Some(chunk) = self.chunks.next() else {
                 return Poll::Ready(Ok(()));
             };
@@ -278,21 +325,21 @@ impl Future for WriteFut<'_, '_> {
 
             if self.chunks.len() == 0 {
                 // Safety: As above.
-                unsafe { SPI1.add(IO).write_volatile(entry) };
+                unsafe { I::BASE.add(IO).write_volatile(entry) };
                 return Poll::Ready(Ok(()));
             } else {
                 // Safety: As above.
-                unsafe { SPI1.add(TXHOLD).write_volatile(entry) };
+                unsafe { I::BASE.add(TXHOLD).write_volatile(entry) };
             }
         }
 
         critical_section::with(|cs| {
-            set_waker(&SPI_WAKER, cx.waker(), cs);
+            set_waker(I::waker(), cx.waker(), cs);
 
             // Safety: Address valid, data barrier used, and we have exclusive access.
             unsafe {
-                let reg = SPI1.add(CONTROL1).read_volatile();
-                SPI1.add(CONTROL1).write_volatile(reg | 1 << 7);
+                let reg = I::BASE.add(CONTROL1).read_volatile();
+                I::BASE.add(CONTROL1).write_volatile(reg | 1 << 7);
             };
         });
 
@@ -300,30 +347,30 @@ impl Future for WriteFut<'_, '_> {
     }
 }
 
-pub struct TransferInPlaceFut<'a, 'b> {
-    spi: &'a mut Spi1,
+pub struct TransferInPlaceFut<'a, 'b, I: Instance> {
+    spi: &'a mut Spi<I>,
     // Guaranteed to be non-empty by the constructor.
     words: (&'b mut [[u8; 3]], &'b mut [u8]),
     tx_index: usize,
     rx_index: usize,
 }
 
-impl Future for TransferInPlaceFut<'_, '_> {
+impl<I: Instance> Future for TransferInPlaceFut<'_, '_, I> {
     type Output = Result<(), Infallible>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         data_memory_barrier();
 
         // Safety: Address valid, data memory barrier used.
-        let out_ms_bit_first = unsafe { SPI1.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
+        let out_ms_bit_first = unsafe { I::BASE.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
         // Safety: As above.
-        let in_ms_bit_first = unsafe { SPI1.add(CONTROL1).read_volatile() } & 1 != 0;
+        let in_ms_bit_first = unsafe { I::BASE.add(CONTROL1).read_volatile() } & 1 != 0;
 
-        while unsafe { SPI1.add(STATUS).read_volatile() >> 7 & 1 } == 0
+        while unsafe { I::BASE.add(STATUS).read_volatile() >> 7 & 1 } == 0
             && self.rx_index <= self.words.0.len()
         {
             // Safety: Address valid, data memory barrier used.
-            let entry = unsafe { SPI1.add(IO).read_volatile() };
+            let entry = unsafe { I::BASE.add(IO).read_volatile() };
             let rx_index = self.rx_index;
             if self.rx_index < self.words.0.len() {
                 from_entry(&mut self.words.0[rx_index], entry, in_ms_bit_first);
@@ -331,47 +378,52 @@ impl Future for TransferInPlaceFut<'_, '_> {
                 from_entry(&mut self.words.1, entry, in_ms_bit_first);
             }
             self.rx_index += 1;
-        } 
+        }
         if self.rx_index > self.words.0.len() {
             return Poll::Ready(Ok(()));
         }
         if self.tx_index > self.words.0.len() {
             critical_section::with(|cs| {
-                set_waker(&SPI_WAKER, cx.waker(), cs);
+                set_waker(I::waker(), cx.waker(), cs);
 
                 // Safety: Address valid, data barrier used, and we have exclusive access.
                 unsafe {
-                    let reg = SPI1.add(CONTROL1).read_volatile();
-                    SPI1.add(CONTROL1).write_volatile(reg | 1 << 6);
+                    let reg = I::BASE.add(CONTROL1).read_volatile();
+                    I::BASE.add(CONTROL1).write_volatile(reg | 1 << 6);
                 };
             });
-            
+
             return Poll::Pending;
         }
-        
-        while unsafe { SPI1.add(STATUS).read_volatile() >> 10 & 1 } == 0
+
+        while unsafe { I::BASE.add(STATUS).read_volatile() >> 10 & 1 } == 0
             && self.tx_index <= self.words.0.len()
         {
-            let words = self.words.0.get(self.tx_index).map(|x| x.as_slice()).unwrap_or(self.words.1);
+            let words = self
+                .words
+                .0
+                .get(self.tx_index)
+                .map(|x| x.as_slice())
+                .unwrap_or(self.words.1);
             let entry = to_entry(words, out_ms_bit_first);
 
             if self.tx_index < self.words.0.len() {
                 // Safety: Address valid, data memory barrier used.
-                unsafe { SPI1.add(TXHOLD).write_volatile(entry) };
+                unsafe { I::BASE.add(TXHOLD).write_volatile(entry) };
             } else {
                 // Safety: Address valid, data memory barrier used.
-                unsafe { SPI1.add(IO).write_volatile(entry) };
+                unsafe { I::BASE.add(IO).write_volatile(entry) };
             }
             self.tx_index += 1;
         }
 
         critical_section::with(|cs| {
-            set_waker(&SPI_WAKER, cx.waker(), cs);
+            set_waker(I::waker(), cx.waker(), cs);
 
             // Safety: Address valid, data barrier used, and we have exclusive access.
             unsafe {
-                let reg = SPI1.add(CONTROL1).read_volatile();
-                SPI1.add(CONTROL1).write_volatile(reg | 1 << 7);
+                let reg = I::BASE.add(CONTROL1).read_volatile();
+                I::BASE.add(CONTROL1).write_volatile(reg | 1 << 7);
             };
         });
 
@@ -379,22 +431,22 @@ impl Future for TransferInPlaceFut<'_, '_> {
     }
 }
 
-pub struct FlushFut<'a> {
-    spi: &'a mut Spi1,
+pub struct FlushFut<'a, I: Instance, M: Mode = Variable> {
+    spi: &'a mut Spi<I, M>,
 }
 
-impl Future for FlushFut<'_> {
+impl<I: Instance, M: Mode> Future for FlushFut<'_, I, M> {
     type Output = Result<(), Infallible>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         data_memory_barrier();
         // Safety: data barrier used.
         if unsafe { self.spi.busy() } {
-            critical_section::with(|cs| set_waker(&SPI_WAKER, cx.waker(), cs));
+            critical_section::with(|cs| set_waker(I::waker(), cx.waker(), cs));
             // Safety: Address valid, data barrier used.
             unsafe {
-                let reg = SPI1.add(CONTROL1).read_volatile();
-                SPI1.add(CONTROL1).write_volatile(reg | 1 << 6);
+                let reg = I::BASE.add(CONTROL1).read_volatile();
+                I::BASE.add(CONTROL1).write_volatile(reg | 1 << 6);
             };
 
             Poll::Pending
@@ -404,73 +456,308 @@ impl Future for FlushFut<'_> {
         }
     }
 }
+impl<I: Instance, const N: u8> hal::spi::SpiBus<u16> for Spi<I, Fixed<N>> {
+    fn read(&mut self, words: &mut [u16]) -> Result<(), Self::Error> {
+        self.transfer_in_place(words)
+    }
+
+    fn write(&mut self, words: &[u16]) -> Result<(), Self::Error> {
+        data_memory_barrier();
+
+        // Safety: Address valid, data memory barrier used.
+        let ms_bit_first = unsafe { I::BASE.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
+        for (index, word) in words.iter().enumerate() {
+            let entry = to_fixed_entry::<N>(*word, ms_bit_first);
+
+            // Safety: As above.
+            while unsafe { I::BASE.add(STATUS).read_volatile() >> 10 & 1 } == 1 {}
+            if index + 1 == words.len() {
+                // Safety: As above.
+                unsafe { I::BASE.add(IO).write_volatile(entry) };
+            } else {
+                // Safety: As above.
+                unsafe { I::BASE.add(TXHOLD).write_volatile(entry) };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u16], write: &[u16]) -> Result<(), Self::Error> {
+        if read.len() >= write.len() {
+            read[0..write.len()].copy_from_slice(write);
+            self.transfer_in_place(read)
+        } else {
+            read.copy_from_slice(&write[0..read.len()]);
+            self.transfer_in_place(read)?;
+            self.write(&write[read.len()..])
+        }
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u16]) -> Result<(), Self::Error> {
+        self.wait_idle();
+
+        // Safety: Address valid, data memory barrier used.
+        let out_ms_bit_first = unsafe { I::BASE.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
+        // Safety: As above.
+        let in_ms_bit_first = unsafe { I::BASE.add(CONTROL1).read_volatile() } >> 1 & 1 != 0;
+        let words_len = words.len();
+        for (index, word) in words.iter_mut().enumerate() {
+            let entry = to_fixed_entry::<N>(*word, out_ms_bit_first);
+
+            if index + 1 == words_len {
+                // Safety: As above.
+                unsafe { I::BASE.add(IO).write_volatile(entry) };
+            } else {
+                // Safety: As above.
+                unsafe { I::BASE.add(TXHOLD).write_volatile(entry) };
+            }
+
+            // Safety: As above.
+            while unsafe { I::BASE.add(STATUS).read_volatile() >> 7 & 1 } == 1 {}
+            // Safety: As above.
+            let entry = unsafe { I::BASE.add(IO).read_volatile() };
+            *word = from_fixed_entry::<N>(entry, in_ms_bit_first);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.wait_idle();
+        Ok(())
+    }
+}
+
+impl<I: Instance, const N: u8> hal_async::spi::SpiBus<u16> for Spi<I, Fixed<N>> {
+    fn read(&mut self, words: &mut [u16]) -> impl Future<Output = Result<(), Self::Error>> {
+        <Self as hal_async::spi::SpiBus<u16>>::transfer_in_place(self, words)
+    }
+
+    async fn write(&mut self, words: &[u16]) -> Result<(), Self::Error> {
+        hal_async::spi::SpiBus::<u16>::flush(self).await?;
+
+        FixedWriteFut {
+            _spi: self,
+            words: words.iter(),
+        }
+        .await
+    }
+
+    async fn transfer(&mut self, read: &mut [u16], write: &[u16]) -> Result<(), Self::Error> {
+        if read.len() >= write.len() {
+            read[0..write.len()].copy_from_slice(write);
+            <Self as hal_async::spi::SpiBus<u16>>::transfer_in_place(self, read).await
+        } else {
+            read.copy_from_slice(&write[0..read.len()]);
+            <Self as hal_async::spi::SpiBus<u16>>::transfer_in_place(self, read).await?;
+            <Self as hal_async::spi::SpiBus<u16>>::write(self, &write[read.len()..]).await
+        }
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u16]) -> Result<(), Self::Error> {
+        hal_async::spi::SpiBus::<u16>::flush(self).await?;
+
+        FixedTransferInPlaceFut {
+            _spi: self,
+            words,
+            tx_index: 0,
+            rx_index: 0,
+        }
+        .await
+    }
+
+    fn flush(&mut self) -> impl Future<Output = Result<(), Self::Error>> {
+        FlushFut { spi: self }
+    }
+}
+
+pub struct FixedWriteFut<'a, 'b, I: Instance, const N: u8> {
+    _spi: &'a mut Spi<I, Fixed<N>>,
+    words: slice::Iter<'b, u16>,
+}
+
+impl<I: Instance, const N: u8> Future for FixedWriteFut<'_, '_, I, N> {
+    type Output = Result<(), Infallible>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        data_memory_barrier();
+
+        // Safety: Address valid, data memory barrier used.
+        let ms_bit_first = unsafe { I::BASE.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
+
+        // Safety: As above.
+        while unsafe { I::BASE.add(STATUS).read_volatile() >> 10 & 1 } == 0 {
+            let Some(word) = self.words.next() else {
+                return Poll::Ready(Ok(()));
+            };
+            let entry = to_fixed_entry::<N>(*word, ms_bit_first);
+
+            if self.words.len() == 0 {
+                // Safety: As above.
+                unsafe { I::BASE.add(IO).write_volatile(entry) };
+                return Poll::Ready(Ok(()));
+            } else {
+                // Safety: As above.
+                unsafe { I::BASE.add(TXHOLD).write_volatile(entry) };
+            }
+        }
+
+        critical_section::with(|cs| {
+            set_waker(I::waker(), cx.waker(), cs);
+
+            // Safety: Address valid, data barrier used, and we have exclusive access.
+            unsafe {
+                let reg = I::BASE.add(CONTROL1).read_volatile();
+                I::BASE.add(CONTROL1).write_volatile(reg | 1 << 7);
+            };
+        });
+
+        Poll::Pending
+    }
+}
+
+pub struct FixedTransferInPlaceFut<'a, 'b, I: Instance, const N: u8> {
+    _spi: &'a mut Spi<I, Fixed<N>>,
+    words: &'b mut [u16],
+    tx_index: usize,
+    rx_index: usize,
+}
+
+impl<I: Instance, const N: u8> Future for FixedTransferInPlaceFut<'_, '_, I, N> {
+    type Output = Result<(), Infallible>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        data_memory_barrier();
+        let Self {
+            words,
+            tx_index,
+            rx_index,
+            ..
+        } = &mut *self;
+
+        // Safety: Address valid, data memory barrier used.
+        let out_ms_bit_first = unsafe { I::BASE.add(CONTROL0).read_volatile() } >> 6 & 1 != 0;
+        // Safety: As above.
+        let in_ms_bit_first = unsafe { I::BASE.add(CONTROL1).read_volatile() } >> 1 & 1 != 0;
+
+        // Safety: As above.
+        while *rx_index < *tx_index && unsafe { I::BASE.add(STATUS).read_volatile() >> 7 & 1 } == 0
+        {
+            // Safety: As above.
+            let entry = unsafe { I::BASE.add(IO).read_volatile() };
+            words[*rx_index] = from_fixed_entry::<N>(entry, in_ms_bit_first);
+            *rx_index += 1;
+        }
+        if *rx_index == words.len() {
+            return Poll::Ready(Ok(()));
+        }
+
+        // Never have more words in flight than the receive FIFO can hold.
+        while *tx_index < words.len()
+            && *tx_index - *rx_index < FIFO_DEPTH
+            // Safety: As above.
+            && unsafe { I::BASE.add(STATUS).read_volatile() >> 10 & 1 } == 0
+        {
+            let entry = to_fixed_entry::<N>(words[*tx_index], out_ms_bit_first);
+            *tx_index += 1;
+            if *tx_index == words.len() {
+                // Safety: As above.
+                unsafe { I::BASE.add(IO).write_volatile(entry) };
+            } else {
+                // Safety: As above.
+                unsafe { I::BASE.add(TXHOLD).write_volatile(entry) };
+            }
+        }
+
+        // Wait for the FIFO to drain, or for the peripheral to be done once everything is sent.
+        let interrupt = if *tx_index == words.len() {
+            1 << 6
+        } else {
+            1 << 7
+        };
+        critical_section::with(|cs| {
+            set_waker(I::waker(), cx.waker(), cs);
+
+            // Safety: Address valid, data barrier used, and we have exclusive access.
+            unsafe {
+                let reg = I::BASE.add(CONTROL1).read_volatile();
+                I::BASE.add(CONTROL1).write_volatile(reg | interrupt);
+            };
+        });
+
+        Poll::Pending
+    }
+}
+
 /// One cannot use both this API and the `SpiBus` API at the same time. If needed, one should call
 /// `clear_fifos` between APIs switches.
-impl hal_nb::spi::FullDuplex for Spi1 {
+impl<I: Instance> hal_nb::spi::FullDuplex for Spi<I> {
     fn read(&mut self) -> hal_nb::nb::Result<u8, Self::Error> {
         data_memory_barrier();
 
         // Safety: Address valid, data barrier used.
-        if unsafe { SPI1.add(STATUS).read_volatile() >> 7 & 1 == 1 } {
+        if unsafe { I::BASE.add(STATUS).read_volatile() >> 7 & 1 == 1 } {
             return Err(hal_nb::nb::Error::WouldBlock);
         }
 
         // Safety: As above.
-        Ok(unsafe { SPI1.add(IO).read_volatile() as u8 })
+        Ok(unsafe { I::BASE.add(IO).read_volatile() as u8 })
     }
 
     fn write(&mut self, word: u8) -> hal_nb::nb::Result<(), Self::Error> {
         data_memory_barrier();
         // Safety: Address valid, data barrier used.
-        if unsafe { SPI1.add(STATUS).read_volatile() >> 10 & 1 == 1 } {
+        if unsafe { I::BASE.add(STATUS).read_volatile() >> 10 & 1 == 1 } {
             return Err(hal_nb::nb::Error::WouldBlock);
         }
         // Safety: As above.
-        unsafe { SPI1.add(IO).write_volatile(word as u32 | 8 << 24) };
+        unsafe { I::BASE.add(IO).write_volatile(word as u32 | 8 << 24) };
         Ok(())
     }
 }
 
 /// One cannot use both this API and the `SpiBus` API at the same time. If needed, one should call
 /// `clear_fifos` between APIs switches.
-impl hal_nb::spi::FullDuplex<u16> for Spi1 {
+impl<I: Instance> hal_nb::spi::FullDuplex<u16> for Spi<I> {
     fn read(&mut self) -> hal_nb::nb::Result<u16, Self::Error> {
         data_memory_barrier();
 
         // Safety: Address valid, data barrier used.
-        if unsafe { SPI1.add(STATUS).read_volatile() >> 7 & 1 == 1 } {
+        if unsafe { I::BASE.add(STATUS).read_volatile() >> 7 & 1 == 1 } {
             return Err(hal_nb::nb::Error::WouldBlock);
         }
 
         // Safety: As above.
-        Ok(unsafe { SPI1.add(IO).read_volatile() as u16 })
+        Ok(unsafe { I::BASE.add(IO).read_volatile() as u16 })
     }
 
     fn write(&mut self, word: u16) -> hal_nb::nb::Result<(), Self::Error> {
         data_memory_barrier();
         // Safety: Address valid, data barrier used.
-        if unsafe { SPI1.add(STATUS).read_volatile() >> 10 & 1 == 1 } {
+        if unsafe { I::BASE.add(STATUS).read_volatile() >> 10 & 1 == 1 } {
             return Err(hal_nb::nb::Error::WouldBlock);
         }
 
         // Safety: As above.
-        unsafe { SPI1.add(IO).write_volatile(word as u32 | 16 << 24) };
+        unsafe { I::BASE.add(IO).write_volatile(word as u32 | 16 << 24) };
         Ok(())
     }
 }
 
-impl Drop for Spi1 {
+impl<I: Instance, M: Mode> Drop for Spi<I, M> {
     fn drop(&mut self) {
         data_memory_barrier();
         let disable = 1 << 11;
         // Safety: Address valid, data barrier used.
-        unsafe { SPI1.add(CONTROL0).write_volatile(disable) };
+        unsafe { I::BASE.add(CONTROL0).write_volatile(disable) };
         critical_section::with(|_| {
             // Safety: As above.
             let aux_enables = unsafe { AUX_ENABLES.read_volatile() };
             // Safety: As above.
-            unsafe { AUX_ENABLES.write_volatile(aux_enables & !0b10) };
-        })
+            unsafe { AUX_ENABLES.write_volatile(aux_enables & !I::AUX_BIT) };
+        });
+        super::release_interrupt();
     }
 }
 
@@ -488,8 +775,92 @@ pub struct Config {
     pub polarity: hal::spi::Polarity,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Speed(u32);
+impl Config {
+    /// A builder for the usual SPI settings.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+/// A [`Config`] described with an SPI mode and a frequency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigBuilder {
+    frequency: u32,
+    mode: hal::spi::Mode,
+    most_significant_first: bool,
+    extra_cs_high_time: CsHighTime,
+    data_out_hold: DataOutHold,
+}
+
+impl Default for ConfigBuilder {
+    /// 1 MHz, SPI mode 0, most significant bit first, no extra CS high time nor data out hold.
+    fn default() -> Self {
+        Self {
+            frequency: 1_000_000,
+            mode: hal::spi::MODE_0,
+            most_significant_first: true,
+            extra_cs_high_time: CsHighTime(0),
+            data_out_hold: DataOutHold::H0,
+        }
+    }
+}
+
+impl ConfigBuilder {
+    /// The maximum frequency of the clock, see [`Speed::from_hz`].
+    pub fn frequency(mut self, hz: u32) -> Self {
+        self.frequency = hz;
+        self
+    }
+
+    /// The clock polarity and phase, one of [`MODE_0`](hal::spi::MODE_0) to
+    /// [`MODE_3`](hal::spi::MODE_3).
+    pub fn mode(mut self, mode: hal::spi::Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The bit order, for both directions.
+    pub fn most_significant_first(mut self, enabled: bool) -> Self {
+        self.most_significant_first = enabled;
+        self
+    }
+
+    pub fn extra_cs_high_time(mut self, time: CsHighTime) -> Self {
+        self.extra_cs_high_time = time;
+        self
+    }
+
+    pub fn data_out_hold(mut self, hold: DataOutHold) -> Self {
+        self.data_out_hold = hold;
+        self
+    }
+
+    /// Compute the clock divider from the core clock.
+    pub fn build(self) -> Result<Config, SpeedError> {
+        // Data is sampled on the rising edge when the clock idles low and is sampled on the first
+        // transition, or when it idles high and is sampled on the second one.
+        let sample_rising = (self.mode.polarity == hal::spi::Polarity::IdleLow)
+            == (self.mode.phase == hal::spi::Phase::CaptureOnFirstTransition);
+
+        Ok(Config {
+            speed: Speed::from_hz(self.frequency)?,
+            post_input: false,
+            data_out_hold: self.data_out_hold,
+            in_rising: sample_rising,
+            out_rising: !sample_rising,
+            out_most_significant_first: self.most_significant_first,
+            in_most_significant_first: self.most_significant_first,
+            extra_cs_high_time: self.extra_cs_high_time,
+            keep_input: false,
+            polarity: self.mode.polarity,
+        })
+    }
+}
+
+/// The frequency of the SPI clock, along with the divider that achieves it.
+///
+/// The controller divides the core clock by `2 * (n + 1)`, with a 12 bit `n`.
+pub type Speed = speed::Speed<8192>;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum DataOutHold {
@@ -499,9 +870,21 @@ pub enum DataOutHold {
     H7 = 3,
 }
 
+/// Additional clock cycles during which CS stays high between transfers.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct CsHighTime(u8);
 
+impl CsHighTime {
+    /// Returns `None` if `cycles` is not in the range `0..=7`.
+    pub fn new(cycles: u8) -> Option<Self> {
+        (cycles <= 7).then_some(Self(cycles))
+    }
+
+    pub fn cycles(&self) -> u8 {
+        self.0
+    }
+}
+
 fn to_entry(slice: &[u8], ms_bit_first: bool) -> u32 {
     let mut entry = 0;
     if ms_bit_first {
@@ -531,6 +914,28 @@ fn from_entry(slice: &mut [u8], entry: u32, ms_bit_first: bool) {
     }
 }
 
+/// The FIFO entry that sends the `N` low bits of `word`.
+fn to_fixed_entry<const N: u8>(word: u16, ms_bit_first: bool) -> u32 {
+    let word = word as u32 & ((1 << N) - 1);
+    // Like in variable mode, outgoing data is aligned on the top of the shift register, which is
+    // 32 bits wide without the length field.
+    if ms_bit_first {
+        word << (32 - N)
+    } else {
+        word
+    }
+}
+
+/// The word received in a FIFO entry.
+fn from_fixed_entry<const N: u8>(entry: u32, ms_bit_first: bool) -> u16 {
+    // Incoming data is shifted in from the bottom when MS bit first, and from the top otherwise.
+    if ms_bit_first {
+        (entry & ((1 << N) - 1)) as u16
+    } else {
+        (entry >> (32 - N)) as u16
+    }
+}
+
 // Unstable functions coming from the `std` lib.
 
 #[inline]
@@ -560,15 +965,15 @@ const unsafe fn as_chunks_unchecked_mut<const N: usize, T>(slice: &mut [T]) -> &
     unsafe { slice::from_raw_parts_mut(slice.as_mut_ptr().cast(), new_len) }
 }
 
-pub(super) fn interrupt_handler() {
+pub(super) fn interrupt_handler<I: Instance>() {
     data_memory_barrier();
     critical_section::with(|cs| {
         // Safety: Address is valid, data memory barrier used, we have exclusive access.
         unsafe {
-            let reg = SPI1.add(CONTROL1).read_volatile();
-            SPI1.add(CONTROL1).write_volatile(reg & !(0b11 << 6))
+            let reg = I::BASE.add(CONTROL1).read_volatile();
+            I::BASE.add(CONTROL1).write_volatile(reg & !(0b11 << 6))
         };
 
-        wake(&SPI_WAKER, cs);
+        wake(I::waker(), cs);
     });
 }
@@ -0,0 +1,132 @@
+//! Devices sharing an aux SPI bus, each with its own chip select.
+//!
+//! The chip selects are driven as GPIO outputs, like the Linux driver does, because the native
+//! ones are deasserted at the end of each FIFO entry, which does not hold across the operations
+//! of a transaction.
+
+use core::convert::Infallible;
+
+use embassy_time::Delay;
+
+use crate::{
+    gpio::{self, state::Output},
+    hal::{self, digital::OutputPin, spi::Operation},
+    hal_async,
+    shared_bus::SharedBus,
+    Sealed,
+};
+
+use super::{
+    instance::{Instance, One, Two},
+    mode::{Mode, Variable},
+    Spi,
+};
+
+/// A device on the aux SPI `I` in mode `M`, selected by `cs`.
+///
+/// The device works on the same words as the bus: bytes in [`Variable`] mode, and `u16` in
+/// [`Fixed<N>`](super::mode::Fixed) mode. The bus is borrowed for the duration of each
+/// transaction.
+pub struct Device<'a, I: Instance, CS, M: Mode = Variable> {
+    bus: &'a SharedBus<Spi<I, M>>,
+    cs: CS,
+}
+
+impl<'a, I: Instance, CS: CsPin<I>, M: Mode> Device<'a, I, CS, M> {
+    /// Create a device on `bus`, and deassert its chip select.
+    pub fn new(bus: &'a SharedBus<Spi<I, M>>, mut cs: CS) -> Self {
+        let Ok(()) = cs.set_high();
+        Self { bus, cs }
+    }
+
+    /// Give back the chip select pin.
+    pub fn release(self) -> CS {
+        self.cs
+    }
+}
+
+impl<I: Instance, CS: CsPin<I>, M: Mode> hal::spi::ErrorType for Device<'_, I, CS, M> {
+    type Error = Infallible;
+}
+
+/// The chip select is asserted for the whole transaction, including the delays.
+impl<W: Copy + 'static, I: Instance, CS: CsPin<I>, M: Mode> hal::spi::SpiDevice<W>
+    for Device<'_, I, CS, M>
+where
+    Spi<I, M>: hal::spi::SpiBus<W, Error = Infallible>,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, W>]) -> Result<(), Self::Error> {
+        use hal::{delay::DelayNs, spi::SpiBus};
+
+        let mut bus = self.bus.borrow();
+        let Ok(()) = self.cs.set_low();
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => bus.read(words)?,
+                Operation::Write(words) => bus.write(words)?,
+                Operation::Transfer(read, write) => bus.transfer(read, write)?,
+                Operation::TransferInPlace(words) => bus.transfer_in_place(words)?,
+                Operation::DelayNs(ns) => {
+                    bus.flush()?;
+                    Delay.delay_ns(*ns);
+                }
+            }
+        }
+        bus.flush()?;
+        let Ok(()) = self.cs.set_high();
+        Ok(())
+    }
+}
+
+/// The chip select is asserted for the whole transaction, including the delays.
+///
+/// Dropping the future in the middle of a transaction leaves the chip select asserted.
+impl<W: Copy + 'static, I: Instance, CS: CsPin<I>, M: Mode> hal_async::spi::SpiDevice<W>
+    for Device<'_, I, CS, M>
+where
+    Spi<I, M>: hal_async::spi::SpiBus<W, Error = Infallible>,
+{
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, W>],
+    ) -> Result<(), Self::Error> {
+        use hal_async::{delay::DelayNs, spi::SpiBus};
+
+        let mut bus = self.bus.wait_borrow().await;
+        let Ok(()) = self.cs.set_low();
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => bus.read(words).await?,
+                Operation::Write(words) => bus.write(words).await?,
+                Operation::Transfer(read, write) => bus.transfer(read, write).await?,
+                Operation::TransferInPlace(words) => bus.transfer_in_place(words).await?,
+                Operation::DelayNs(ns) => {
+                    bus.flush().await?;
+                    Delay.delay_ns(*ns).await;
+                }
+            }
+        }
+        bus.flush().await?;
+        let Ok(()) = self.cs.set_high();
+        Ok(())
+    }
+}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as a chip select of the aux SPI
+/// `I`.
+#[allow(private_bounds)]
+pub trait CsPin<I: Instance>: Sealed + OutputPin<Error = Infallible> {}
+
+// See the BCM2835 manual section 6.2 for the pin mappings.
+/// CE2
+impl CsPin<One> for gpio::Pin<16, Output> {}
+/// CE1
+impl CsPin<One> for gpio::Pin<17, Output> {}
+/// CE0
+impl CsPin<One> for gpio::Pin<18, Output> {}
+/// CE0
+impl CsPin<Two> for gpio::Pin<43, Output> {}
+/// CE1
+impl CsPin<Two> for gpio::Pin<44, Output> {}
+/// CE2
+impl CsPin<Two> for gpio::Pin<45, Output> {}
@@ -0,0 +1,61 @@
+use crate::{
+    gpio::{self, state::Alternate4},
+    impl_sealed, Sealed, WakerCell, WAKER_CELL_INIT,
+};
+
+use super::registers::{SPI1, SPI2};
+
+static SPI1_WAKER: WakerCell = WAKER_CELL_INIT;
+static SPI2_WAKER: WakerCell = WAKER_CELL_INIT;
+
+/// One of the two aux SPI peripherals.
+#[allow(private_bounds)]
+pub trait Instance: Sealed + Registers {}
+
+pub(super) trait Registers {
+    /// The MISO, MOSI and SCLK pins.
+    type Pins;
+    const BASE: *mut u32;
+    /// The bit of the peripheral in the `AUX_ENABLES` and `AUX_INTERRUPT_STATUS` registers.
+    const AUX_BIT: u32;
+
+    fn waker() -> &'static WakerCell;
+}
+
+/// The first aux SPI, see [`Spi1`](super::Spi1).
+pub struct One;
+/// The second aux SPI, see [`Spi2`](super::Spi2).
+pub struct Two;
+
+impl_sealed!(One, Two);
+
+impl Instance for One {}
+impl Instance for Two {}
+
+impl Registers for One {
+    type Pins = (
+        gpio::Pin<19, Alternate4>,
+        gpio::Pin<20, Alternate4>,
+        gpio::Pin<21, Alternate4>,
+    );
+    const BASE: *mut u32 = SPI1;
+    const AUX_BIT: u32 = 1 << 1;
+
+    fn waker() -> &'static WakerCell {
+        &SPI1_WAKER
+    }
+}
+
+impl Registers for Two {
+    type Pins = (
+        gpio::Pin<40, Alternate4>,
+        gpio::Pin<41, Alternate4>,
+        gpio::Pin<42, Alternate4>,
+    );
+    const BASE: *mut u32 = SPI2;
+    const AUX_BIT: u32 = 1 << 2;
+
+    fn waker() -> &'static WakerCell {
+        &SPI2_WAKER
+    }
+}
@@ -5,11 +5,31 @@ pub trait Mode: Sealed {
     const CONFIG_MASK: u32;
 }
 
+/// Each burst is as long as the data written to the FIFO, up to 24 bits.
 pub struct Variable;
 
 impl_sealed!(Variable);
 
 impl Mode for Variable {
-    // This turns on both the Variable length and variable CS modes in the CNTL0 register
-    const CONFIG_MASK: u32 = 0b11 << 14;
+    // This turns on the Variable length mode in the CNTL0 register. The chip selects stay those of
+    // CNTL0, instead of coming from the FIFO entries.
+    const CONFIG_MASK: u32 = 1 << 14;
+}
+
+/// Each burst is `N` bits long, for devices with frames that are not made of bytes.
+///
+/// `N` must be in the range `1..=16`.
+pub struct Fixed<const N: u8>;
+
+impl<const N: u8> Sealed for Fixed<N> {}
+
+impl<const N: u8> Mode for Fixed<N> {
+    // This sets the shift length in the CNTL0 register.
+    const CONFIG_MASK: u32 = {
+        assert!(
+            N >= 1 && N <= 16,
+            "the burst length must be in the range 1..=16"
+        );
+        N as u32
+    };
 }
@@ -1,23 +1,34 @@
 //! The Mini UART peripheral, also referred to as `UART1`.
 
+pub mod buffered;
+pub mod flow;
 pub mod reader;
 mod registers;
 pub mod writer;
 
-pub use reader::Reader;
+pub use buffered::{BufferedReader, BufferedWriter};
+pub use reader::{Reader, Status};
 pub use writer::Writer;
 
-use core::ptr::{read_volatile, write_volatile};
+use core::{
+    ptr::{read_volatile, write_volatile},
+    sync::atomic::{AtomicU32, Ordering},
+};
 
-use crate::{aux::uart::registers::*, data_memory_barrier};
+use crate::{
+    aux::uart::registers::*,
+    data_memory_barrier,
+    mailbox::{self, Clock},
+    serial::{Parity, StopBits, WordLength},
+};
 
 use critical_section::CriticalSection;
+use flow::FlowControl;
 use reader::RxPin;
 use writer::TxPin;
 
-// This is the clock speed of the "system clock," which is the VPU clock (Video Core).
-// TODO: This should be configurable at build time.
-pub const CLOCK_SPEED: u32 = 250_000_000;
+/// The baud rate requested by the configuration of the Mini UART, applied when it was acquired.
+static REQUESTED_BAUD_RATE: AtomicU32 = AtomicU32::new(0);
 
 pub fn pair<RP: RxPin, TP: TxPin>(
     rx_pin: RP,
@@ -45,14 +56,18 @@ pub fn pair<RP: RxPin, TP: TxPin>(
     })?;
 
     config.setup();
+    super::register_interrupt();
 
     Some((
-        reader::Reader { _rx_pin: rx_pin },
+        reader::Reader {
+            rx_pin,
+            detect_breaks: false,
+        },
         writer::Writer { _tx_pin: tx_pin },
     ))
 }
 
-/// Safety: Must be called before main.
+/// Safety: Must be called before the Mini UART is used.
 pub(super) unsafe fn setup(_cs: &CriticalSection) {
     // Disable RX and TX.
     data_memory_barrier();
@@ -62,53 +77,135 @@ pub(super) unsafe fn setup(_cs: &CriticalSection) {
     }
 }
 
+/// Whether the receiver or the transmitter is enabled.
+pub(super) fn in_use() -> bool {
+    data_memory_barrier();
+    // Safety: Address valid, data memory barrier used.
+    let control_reg = unsafe { read_volatile(EXTRA_CONTROL_REG) };
+    data_memory_barrier();
+    control_reg & 0b11 != 0
+}
+
 // Handle interrupts that pertain to the Mini UART peripheral.
 pub(super) fn interrupt_handler() {
     data_memory_barrier();
     // Safety: Address is valid, data memory barrier used.
     let interrupt_id = unsafe { read_volatile(INTERRUPT_ID_REG) };
     let interrupt_mask = (interrupt_id >> 1) & 0b11;
-    if interrupt_mask & 0b1 != 0 {
+    // The buffered reader and writer move the data themselves, the others are only woken up.
+    if interrupt_mask & 0b1 != 0 && !buffered::transmit_interrupt() {
         writer::interrupt_handler();
     }
-    if interrupt_mask & 0b10 != 0 {
-        reader::interrupt_handler();
+    if interrupt_mask & 0b10 != 0 && !buffered::receive_interrupt() {
+        // Safety: We are the interrupt handler.
+        unsafe { reader::interrupt_handler() };
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub enum BitMode {
-    #[default]
     SevenBits = 0,
+    #[default]
     EightBits = 3,
 }
 
+/// The baud rate of the Mini UART, along with the divisor that achieves it.
+///
+/// The Mini UART is clocked by the core clock (the VPU clock), so the achievable baud rates depend
+/// on its frequency, which can be changed in `config.txt` or throttled by the firmware.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct BaudRate(u32);
+pub struct BaudRate {
+    requested: u32,
+    clock: u32,
+    register: u16,
+}
 
 impl BaudRate {
-    pub fn new(baud_rate: u32) -> Self {
-        // TODO: This should depend on the clock speed.
-        assert!(
-            (476..=31_250_000).contains(&baud_rate),
-            "baud rate not in the range 476..=31_250_000"
-        );
-        Self(baud_rate)
+    /// Compute the divisor for `baud_rate` from the core clock, queried from the firmware.
+    pub fn new(baud_rate: u32) -> Result<Self, BaudRateError> {
+        let clock = mailbox::clock_rate(Clock::Core).ok_or(BaudRateError::UnknownClock)?;
+        Self::with_clock(baud_rate, clock)
+    }
+
+    /// Compute the divisor for `baud_rate` from a core clock running at `clock` Hz.
+    pub fn with_clock(baud_rate: u32, clock: u32) -> Result<Self, BaudRateError> {
+        // baud rate = clock / (8 * (register + 1)), with a 16 bit register.
+        let divisor = (clock as u64 + 4 * baud_rate as u64)
+            .checked_div(8 * baud_rate as u64)
+            .unwrap_or(0);
+        if !(1..=1 << 16).contains(&divisor) {
+            return Err(BaudRateError::OutOfRange {
+                min: clock.div_ceil(8 << 16),
+                max: clock / 8,
+            });
+        }
+
+        Ok(Self {
+            requested: baud_rate,
+            clock,
+            register: (divisor - 1) as u16,
+        })
+    }
+
+    /// The baud rate that was asked for.
+    pub fn requested(&self) -> u32 {
+        self.requested
+    }
+
+    /// The baud rate that the divisor actually achieves.
+    pub fn achieved(&self) -> u32 {
+        self.clock / (8 * (self.register as u32 + 1))
+    }
+
+    /// The core clock the divisor was computed from, in Hz.
+    pub fn clock(&self) -> u32 {
+        self.clock
+    }
+
+    /// The relative error of the achieved baud rate, in percent.
+    pub fn error_percent(&self) -> f32 {
+        (self.achieved() as f32 - self.requested as f32) / self.requested as f32 * 100.0
     }
 
     fn register_value(&self) -> u32 {
-        CLOCK_SPEED / (8 * self.0) - 1
+        self.register as u32
     }
 }
 
+/// The reasons a baud rate cannot be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BaudRateError {
+    /// The firmware did not report the core clock.
+    UnknownClock,
+    /// The baud rate is not reachable with the current core clock.
+    OutOfRange { min: u32, max: u32 },
+}
+
+/// Recompute the divisor of the Mini UART from the current core clock, and apply it.
+///
+/// Must only be called by the reader or the writer, which own the Mini UART.
+fn refresh_baud_rate() -> Result<BaudRate, BaudRateError> {
+    let baud_rate = BaudRate::new(REQUESTED_BAUD_RATE.load(Ordering::Relaxed))?;
+
+    data_memory_barrier();
+    // Safety: Valid address used, data memory barrier used. The Mini UART is in use, so it was
+    // configured with the requested baud rate.
+    unsafe { write_volatile(BAUDRATE_REG, baud_rate.register_value()) };
+    Ok(baud_rate)
+}
+
 pub struct Config {
-    /// Panics if the baud rate is not in the range `476..=31_250_000`.
     pub baud_rate: BaudRate,
     pub bit_mode: BitMode,
-    // TODO: Control flow
+    pub flow_control: FlowControl,
 }
 
 impl Config {
+    /// A builder that checks the configuration against what the Mini UART can do.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
     fn setup(&self) {
         data_memory_barrier();
         // Safety: Valid address used, data memory barrier used.
@@ -116,5 +213,103 @@ impl Config {
             write_volatile(LINE_CONTROL_REG, self.bit_mode as u32);
             write_volatile(BAUDRATE_REG, self.baud_rate.register_value());
         }
+        REQUESTED_BAUD_RATE.store(self.baud_rate.requested, Ordering::Relaxed);
+        critical_section::with(|_| {
+            // Safety: As above. The receiver and transmitter enable bits are left untouched.
+            unsafe {
+                let control_reg = read_volatile(EXTRA_CONTROL_REG);
+                write_volatile(
+                    EXTRA_CONTROL_REG,
+                    control_reg & 0b11 | self.flow_control.register_value(),
+                );
+            }
+        });
+    }
+}
+
+/// A [`Config`] described with the usual UART settings.
+///
+/// The Mini UART only does 7 and 8 bit words, without parity, with one stop bit.
+/// [`ConfigBuilder::build`] rejects anything else, instead of silently sending a different
+/// framing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ConfigBuilder {
+    baud_rate: u32,
+    word_length: WordLength,
+    parity: Parity,
+    stop_bits: StopBits,
+    flow_control: FlowControl,
+}
+
+impl Default for ConfigBuilder {
+    /// 115200 baud, 8 bits, no parity, one stop bit, no flow control.
+    fn default() -> Self {
+        Self {
+            baud_rate: 115200,
+            word_length: WordLength::EightBits,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+impl ConfigBuilder {
+    pub fn baud_rate(mut self, baud_rate: u32) -> Self {
+        self.baud_rate = baud_rate;
+        self
     }
+
+    pub fn word_length(mut self, word_length: WordLength) -> Self {
+        self.word_length = word_length;
+        self
+    }
+
+    pub fn parity(mut self, parity: Parity) -> Self {
+        self.parity = parity;
+        self
+    }
+
+    pub fn stop_bits(mut self, stop_bits: StopBits) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    pub fn flow_control(mut self, flow_control: FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    /// Check the settings, and compute the baud rate divisor from the core clock.
+    pub fn build(self) -> Result<Config, ConfigError> {
+        let bit_mode = match self.word_length {
+            WordLength::SevenBits => BitMode::SevenBits,
+            WordLength::EightBits => BitMode::EightBits,
+            word_length => return Err(ConfigError::UnsupportedWordLength(word_length)),
+        };
+        if self.parity != Parity::None {
+            return Err(ConfigError::UnsupportedParity(self.parity));
+        }
+        if self.stop_bits != StopBits::One {
+            return Err(ConfigError::UnsupportedStopBits(self.stop_bits));
+        }
+
+        Ok(Config {
+            baud_rate: BaudRate::new(self.baud_rate).map_err(ConfigError::BaudRate)?,
+            bit_mode,
+            flow_control: self.flow_control,
+        })
+    }
+}
+
+/// The reasons a [`ConfigBuilder`] is rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConfigError {
+    /// Only 7 and 8 bit words are supported.
+    UnsupportedWordLength(WordLength),
+    /// The Mini UART has no parity bit, it is neither sent nor checked.
+    UnsupportedParity(Parity),
+    /// The Mini UART always sends one stop bit.
+    UnsupportedStopBits(StopBits),
+    BaudRate(BaudRateError),
 }
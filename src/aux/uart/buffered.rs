@@ -0,0 +1,334 @@
+//! Reader and writer backed by ring buffers, that the interrupt handler fills and drains.
+//!
+//! The Mini UART only has 8 byte FIFOs, which overrun quickly when the reading task is not polled
+//! in time. With these, bursts of up to the size of the buffer are never dropped.
+
+use core::{
+    cell::Cell,
+    convert::Infallible,
+    future::poll_fn,
+    ptr::{read_volatile, write_volatile},
+    slice,
+    task::Poll,
+};
+
+use critical_section::{CriticalSection, Mutex};
+
+use crate::{
+    aux::uart::{
+        reader::{self, Error, RxPin},
+        registers::*,
+        writer::TxPin,
+        Reader, Writer,
+    },
+    data_memory_barrier, eio, eio_async, set_waker, wake, WakerCell, WAKER_CELL_INIT,
+};
+
+static RX_RING: RingCell = Mutex::new(Cell::new(None));
+static TX_RING: RingCell = Mutex::new(Cell::new(None));
+static RX_WAKER: WakerCell = WAKER_CELL_INIT;
+static TX_WAKER: WakerCell = WAKER_CELL_INIT;
+
+type RingCell = Mutex<Cell<Option<Ring>>>;
+
+/// A ring buffer over a buffer given by the user.
+#[derive(Clone, Copy)]
+struct Ring {
+    buf: *mut u8,
+    capacity: usize,
+    start: usize,
+    len: usize,
+    /// Some bytes were dropped because the buffer or the FIFO was full.
+    overrun: bool,
+}
+
+// Safety: The buffer comes from a `&'static mut [u8]`, which is only accessed through the ring.
+unsafe impl Send for Ring {}
+
+impl Ring {
+    fn new(buf: &'static mut [u8]) -> Self {
+        assert!(!buf.is_empty(), "the buffer must not be empty");
+        Ring {
+            buf: buf.as_mut_ptr(),
+            capacity: buf.len(),
+            start: 0,
+            len: 0,
+            overrun: false,
+        }
+    }
+
+    /// Returns `false` if the ring is full.
+    fn push(&mut self, byte: u8) -> bool {
+        if self.len == self.capacity {
+            return false;
+        }
+        // Safety: The index is in bounds of the buffer.
+        unsafe {
+            self.buf
+                .add((self.start + self.len) % self.capacity)
+                .write(byte)
+        };
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        // Safety: The index is in bounds of the buffer.
+        let byte = unsafe { self.buf.add(self.start).read() };
+        self.consume(1);
+        Some(byte)
+    }
+
+    /// The oldest bytes of the ring that are contiguous in the buffer.
+    fn contiguous(&self) -> (*const u8, usize) {
+        // Safety: The start is in bounds of the buffer.
+        let start = unsafe { self.buf.add(self.start) };
+        (start, self.len.min(self.capacity - self.start))
+    }
+
+    fn consume(&mut self, amount: usize) {
+        let amount = amount.min(self.len);
+        self.start = (self.start + amount) % self.capacity;
+        self.len -= amount;
+    }
+}
+
+/// Run `f` on the installed ring.
+fn with_ring<R>(ring: &RingCell, cs: CriticalSection, f: impl FnOnce(&mut Ring) -> R) -> R {
+    let cell = ring.borrow(cs);
+    let mut state = cell.get().expect("the ring buffer is installed");
+    let result = f(&mut state);
+    cell.set(Some(state));
+    result
+}
+
+/// Set or clear `bits` in the interrupt enable register.
+fn set_interrupts(bits: u32, enabled: bool) {
+    data_memory_barrier();
+    // Safety: Address is valid, data memory barrier used. Called inside of a critical section.
+    unsafe {
+        let mut reg = INTERRUPT_ENABLE_REG.read_volatile();
+        if enabled {
+            reg |= bits;
+        } else {
+            reg &= !bits;
+        }
+        INTERRUPT_ENABLE_REG.write_volatile(reg);
+    }
+}
+
+/// A [`Reader`] whose received bytes are stored in a buffer by the interrupt handler.
+#[derive(Debug)]
+pub struct BufferedReader<P> {
+    _reader: Reader<P>,
+}
+
+impl<P: RxPin> BufferedReader<P> {
+    /// Store the bytes received by `reader` in `buffer`.
+    ///
+    /// Panics if `buffer` is empty.
+    pub fn new(reader: Reader<P>, buffer: &'static mut [u8]) -> Self {
+        critical_section::with(|cs| {
+            RX_RING.borrow(cs).set(Some(Ring::new(buffer)));
+            // We use 1101 because the errata says that bit 2 and 3 should be set to 1.
+            set_interrupts(0b1101, true);
+        });
+        Self { _reader: reader }
+    }
+}
+
+impl<P> Drop for BufferedReader<P> {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            set_interrupts(0b1, false);
+            RX_RING.borrow(cs).set(None);
+        });
+    }
+}
+
+impl<P: RxPin> eio::ErrorType for BufferedReader<P> {
+    type Error = Error;
+}
+
+/// An overrun is reported once, the bytes received before and after it stay in the buffer.
+impl<P: RxPin> eio_async::BufRead for BufferedReader<P> {
+    async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        let (data, len) = poll_fn(|cx| {
+            critical_section::with(|cs| {
+                with_ring(&RX_RING, cs, |ring| {
+                    if ring.overrun {
+                        ring.overrun = false;
+                        Poll::Ready(Err(Error::Overrun))
+                    } else if ring.len == 0 {
+                        set_waker(&RX_WAKER, cx.waker(), cs);
+                        Poll::Pending
+                    } else {
+                        Poll::Ready(Ok(ring.contiguous()))
+                    }
+                })
+            })
+        })
+        .await?;
+        // Safety: The interrupt handler only writes past the buffered bytes, and they are only
+        // released by `consume`, which borrows `self` mutably.
+        Ok(unsafe { slice::from_raw_parts(data, len) })
+    }
+
+    fn consume(&mut self, amt: usize) {
+        critical_section::with(|cs| with_ring(&RX_RING, cs, |ring| ring.consume(amt)));
+    }
+}
+
+/// This implementation is cancel-safe.
+impl<P: RxPin> eio_async::Read for BufferedReader<P> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let data = eio_async::BufRead::fill_buf(self).await?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        eio_async::BufRead::consume(self, len);
+        Ok(len)
+    }
+}
+
+/// Move the received bytes to the buffer.
+///
+/// Returns `false` if there is no [`BufferedReader`].
+pub(super) fn receive_interrupt() -> bool {
+    critical_section::with(|cs| {
+        let cell = RX_RING.borrow(cs);
+        let Some(mut ring) = cell.get() else {
+            return false;
+        };
+        data_memory_barrier();
+        loop {
+            // Safety: Address is valid, data memory barrier used.
+            let status_reg = unsafe { read_volatile(LINE_STATUS_REG) };
+            if status_reg & 0b10 != 0 {
+                ring.overrun = true;
+                reader::record_dropped(1);
+            }
+            if status_reg & 1 == 0 {
+                break;
+            }
+            // Safety: As above.
+            let byte = unsafe { read_volatile(IO_REG) as u8 };
+            if !ring.push(byte) {
+                ring.overrun = true;
+                reader::record_dropped(1);
+            }
+        }
+        data_memory_barrier();
+        cell.set(Some(ring));
+        wake(&RX_WAKER, cs);
+        true
+    })
+}
+
+/// A [`Writer`] whose bytes are stored in a buffer, and sent by the interrupt handler.
+///
+/// The bytes that are still buffered when it is dropped are discarded, flush it first.
+#[derive(Debug)]
+pub struct BufferedWriter<P> {
+    _writer: Writer<P>,
+}
+
+impl<P: TxPin> BufferedWriter<P> {
+    /// Store the bytes to send with `writer` in `buffer`.
+    ///
+    /// Panics if `buffer` is empty.
+    pub fn new(writer: Writer<P>, buffer: &'static mut [u8]) -> Self {
+        critical_section::with(|cs| TX_RING.borrow(cs).set(Some(Ring::new(buffer))));
+        Self { _writer: writer }
+    }
+}
+
+impl<P> Drop for BufferedWriter<P> {
+    fn drop(&mut self) {
+        critical_section::with(|cs| {
+            set_interrupts(0b10, false);
+            TX_RING.borrow(cs).set(None);
+        });
+    }
+}
+
+impl<P: TxPin> eio::ErrorType for BufferedWriter<P> {
+    type Error = Infallible;
+}
+
+/// This implementation is cancel-safe.
+impl<P: TxPin> eio_async::Write for BufferedWriter<P> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                let written = with_ring(&TX_RING, cs, |ring| {
+                    buf.iter().take_while(|byte| ring.push(**byte)).count()
+                });
+                if written == 0 {
+                    set_waker(&TX_WAKER, cx.waker(), cs);
+                    return Poll::Pending;
+                }
+                // The interrupt triggers once the transmit FIFO is empty.
+                // We use 1110 because the errata says that bit 2 and 3 should be set to 1.
+                set_interrupts(0b1110, true);
+                Poll::Ready(Ok(written))
+            })
+        })
+        .await
+    }
+
+    /// The Mini UART has no interrupt for the end of a transmission, so this polls once the
+    /// buffer and the transmit FIFO are empty.
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                if with_ring(&TX_RING, cs, |ring| ring.len) != 0 {
+                    set_waker(&TX_WAKER, cx.waker(), cs);
+                    return Poll::Pending;
+                }
+                data_memory_barrier();
+                // Safety: Address is valid, memory barrier used.
+                let status = unsafe { EXTRA_STATUS_REG.read_volatile() };
+                data_memory_barrier();
+                if (status >> 9) & 1 == 0 {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Poll::Ready(Ok(()))
+            })
+        })
+        .await
+    }
+}
+
+/// Move bytes from the buffer to the transmit FIFO.
+///
+/// Returns `false` if there is no [`BufferedWriter`].
+pub(super) fn transmit_interrupt() -> bool {
+    critical_section::with(|cs| {
+        let cell = TX_RING.borrow(cs);
+        let Some(mut ring) = cell.get() else {
+            return false;
+        };
+        data_memory_barrier();
+        // Safety: Address is valid, data memory barrier used.
+        while unsafe { read_volatile(EXTRA_STATUS_REG) } & 0b10 != 0 {
+            let Some(byte) = ring.pop() else {
+                break;
+            };
+            // Safety: As above.
+            unsafe { write_volatile(IO_REG, byte as u32) };
+        }
+        if ring.len == 0 {
+            set_interrupts(0b10, false);
+        }
+        cell.set(Some(ring));
+        wake(&TX_WAKER, cs);
+        true
+    })
+}
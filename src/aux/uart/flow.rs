@@ -0,0 +1,130 @@
+//! Hardware flow control with the RTS and CTS lines.
+
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::{
+    aux::uart::registers::*,
+    data_memory_barrier,
+    gpio::{self, state::Alternate5},
+    Sealed,
+};
+
+/// How the RTS and CTS lines are driven.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum FlowControl {
+    /// The UART ignores RTS and CTS. They can still be handled manually with [`Rts`] and [`Cts`].
+    #[default]
+    None,
+    /// The receiver deasserts RTS when its FIFO fills up to `rts_level`, and the transmitter
+    /// only sends while CTS is asserted.
+    Auto {
+        rts_level: RtsLevel,
+        rts_polarity: Polarity,
+        cts_polarity: Polarity,
+    },
+}
+
+impl FlowControl {
+    /// The flow control bits of the extra control register.
+    pub(super) fn register_value(&self) -> u32 {
+        match *self {
+            FlowControl::None => 0,
+            FlowControl::Auto {
+                rts_level,
+                rts_polarity,
+                cts_polarity,
+            } => {
+                0b1100
+                    | (rts_level as u32) << 4
+                    | (rts_polarity as u32) << 6
+                    | (cts_polarity as u32) << 7
+            }
+        }
+    }
+}
+
+/// The number of free entries left in the receive FIFO when RTS is deasserted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum RtsLevel {
+    OneFree = 2,
+    TwoFree = 1,
+    #[default]
+    ThreeFree = 0,
+    FourFree = 3,
+}
+
+/// The level of an asserted line, as described by the BCM2835 manual.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Polarity {
+    #[default]
+    High = 0,
+    Low = 1,
+}
+
+/// Manual control of the RTS line.
+#[derive(Debug)]
+pub struct Rts<P> {
+    _rts_pin: P,
+}
+
+impl<P: RtsPin> Rts<P> {
+    pub fn new(rts_pin: P) -> Self {
+        Self { _rts_pin: rts_pin }
+    }
+
+    /// Drive the RTS line low when `asserted`, and high otherwise.
+    ///
+    /// This has no effect while [`FlowControl::Auto`] is used.
+    pub fn set_rts(&mut self, asserted: bool) {
+        data_memory_barrier();
+        critical_section::with(|_| {
+            // Safety: Address is valid, data memory barrier used.
+            unsafe {
+                let mut reg = read_volatile(MODEM_CONTROL_REG);
+                if asserted {
+                    reg |= 0b10;
+                } else {
+                    reg &= !0b10;
+                }
+                write_volatile(MODEM_CONTROL_REG, reg);
+            }
+        });
+    }
+}
+
+/// Manual reading of the CTS line.
+#[derive(Debug)]
+pub struct Cts<P> {
+    _cts_pin: P,
+}
+
+impl<P: CtsPin> Cts<P> {
+    pub fn new(cts_pin: P) -> Self {
+        Self { _cts_pin: cts_pin }
+    }
+
+    /// Whether the CTS line is low.
+    pub fn cts_asserted(&self) -> bool {
+        data_memory_barrier();
+        // Safety: Address is valid, data memory barrier used.
+        let status = unsafe { read_volatile(MODEM_STATUS_REG) };
+        data_memory_barrier();
+        // The status bit is the inverse of the line.
+        status & (1 << 4) != 0
+    }
+}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the Mini UART RTS pin.
+#[allow(private_bounds)]
+pub trait RtsPin: Sealed {}
+
+// See the BCM2835 manual section 6.2 for the pin mappings.
+impl RtsPin for gpio::Pin<17, Alternate5> {}
+impl RtsPin for gpio::Pin<31, Alternate5> {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the Mini UART CTS pin.
+#[allow(private_bounds)]
+pub trait CtsPin: Sealed {}
+
+impl CtsPin for gpio::Pin<16, Alternate5> {}
+impl CtsPin for gpio::Pin<30, Alternate5> {}
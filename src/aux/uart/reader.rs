@@ -1,24 +1,43 @@
 use core::{
+    cell::Cell,
     future::Future,
     ptr::{read_volatile, write_volatile},
     slice::IterMut,
     task::Poll,
 };
 
+use critical_section::Mutex;
 use embedded_io::ReadExactError;
 
 use crate::{
-    aux::uart::{registers::*, Config},
+    aux::uart::{registers::*, BaudRate, BaudRateError, Config},
     data_memory_barrier, eio, eio_async,
-    gpio::{self, state::Alternate5},
+    gpio::{self, state::Alternate5, Level},
     hal_nb, set_waker, wake, Sealed, WakerCell, WAKER_CELL_INIT,
 };
 
 static READER_WAKER: WakerCell = WAKER_CELL_INIT;
+/// The number of bytes known to be lost, see [`Status::dropped`].
+static DROPPED: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+/// Count `count` bytes as lost.
+pub(super) fn record_dropped(count: u32) {
+    critical_section::with(|cs| {
+        let dropped = DROPPED.borrow(cs);
+        dropped.set(dropped.get().wrapping_add(count));
+    });
+}
+
+/// Record an overrun, and return the error for it.
+fn overrun() -> Error {
+    record_dropped(1);
+    Error::Overrun
+}
 
 #[derive(Debug)]
 pub struct Reader<P> {
-    pub(super) _rx_pin: P,
+    pub(super) rx_pin: P,
+    pub(super) detect_breaks: bool,
 }
 
 impl<P: RxPin> Reader<P> {
@@ -44,8 +63,12 @@ impl<P: RxPin> Reader<P> {
         })?;
 
         config.setup();
+        crate::aux::register_interrupt();
 
-        Some(Self { _rx_pin: rx_pin })
+        Some(Self {
+            rx_pin,
+            detect_breaks: false,
+        })
     }
 
     /// Get the reader without checking if it is already in use.
@@ -68,8 +91,69 @@ impl<P: RxPin> Reader<P> {
         });
 
         config.setup();
+        crate::aux::register_interrupt();
+
+        Self {
+            rx_pin,
+            detect_breaks: false,
+        }
+    }
+
+    /// Report breaks as [`Error::Break`], instead of as a zero byte.
+    ///
+    /// The Mini UART cannot detect breaks, so a break is assumed when a zero byte is received,
+    /// the receive FIFO is empty, and the RX line is still low. This is only reliable when the
+    /// zero byte is read before the break ends, and a zero byte followed right away by another
+    /// byte can be mistaken for a break. It is disabled by default.
+    pub fn detect_breaks(&mut self, enabled: bool) {
+        self.detect_breaks = enabled;
+    }
+
+    /// Recompute the baud rate divisor from the current core clock, and apply it.
+    ///
+    /// Call this after the core clock changed. The divisor is shared with the writer.
+    pub fn refresh_baud_rate(&mut self) -> Result<BaudRate, BaudRateError> {
+        super::refresh_baud_rate()
+    }
+
+    /// The state of the receiver.
+    ///
+    /// This does not consume any byte, but reading it clears a pending overrun, which is then
+    /// only accounted for in [`Status::dropped`].
+    pub fn status(&self) -> Status {
+        data_memory_barrier();
+        // Safety: Addresses are valid, data memory barrier used.
+        let (status_reg, extra_status_reg) = unsafe {
+            (
+                read_volatile(LINE_STATUS_REG),
+                read_volatile(EXTRA_STATUS_REG),
+            )
+        };
+        if status_reg & 0b10 != 0 {
+            record_dropped(1);
+        }
+        data_memory_barrier();
 
-        Self { _rx_pin: rx_pin }
+        Status {
+            idle: extra_status_reg & 0b100 != 0,
+            fifo_level: ((extra_status_reg >> 16) & 0xF) as u8,
+            dropped: critical_section::with(|cs| DROPPED.borrow(cs).get()),
+        }
+    }
+
+    /// Check whether a byte that was just received is a break.
+    fn check_break(&self, byte: u8) -> Result<u8, Error> {
+        if !self.detect_breaks || byte != 0 {
+            return Ok(byte);
+        }
+        data_memory_barrier();
+        // Safety: Address is valid, data memory barrier used.
+        let status_reg = unsafe { read_volatile(LINE_STATUS_REG) };
+        if status_reg & 1 == 0 && !self.rx_pin.level() {
+            Err(Error::Break)
+        } else {
+            Ok(byte)
+        }
     }
 }
 
@@ -85,7 +169,8 @@ impl<P> Drop for Reader<P> {
                 let control_reg = read_volatile(EXTRA_CONTROL_REG);
                 write_volatile(EXTRA_CONTROL_REG, control_reg & !1);
             };
-        })
+        });
+        crate::aux::release_interrupt();
     }
 }
 
@@ -105,10 +190,10 @@ impl<P: RxPin> eio::Read for Reader<P> {
                 // Safety: Address is valid, data memory barrier used.
                 let status_reg = unsafe { read_volatile(LINE_STATUS_REG) };
                 if status_reg & 0b10 != 0 {
-                    return Err(Error::Overrun);
+                    return Err(overrun());
                 } else if status_reg & 1 != 0 {
                     // Safety: As above.
-                    *byte = unsafe { read_volatile(IO_REG) as u8 };
+                    *byte = self.check_break(unsafe { read_volatile(IO_REG) as u8 })?;
                     break;
                 } else if count != 0 {
                     return Ok(count);
@@ -128,13 +213,13 @@ impl<P: RxPin> eio::Read for Reader<P> {
                 // Safety: Address valid, data memory barrier used.
                 let status_reg = unsafe { read_volatile(LINE_STATUS_REG) };
                 if status_reg & 0b10 != 0 {
-                    return Err(Error::Overrun.into());
+                    return Err(overrun().into());
                 } else if status_reg & 1 != 0 {
                     break;
                 }
             }
             // Safety: As above.
-            *byte = unsafe { read_volatile(IO_REG) as u8 };
+            *byte = self.check_break(unsafe { read_volatile(IO_REG) as u8 })?;
         }
         Ok(())
     }
@@ -154,10 +239,11 @@ impl<P: RxPin> hal_nb::serial::Read for Reader<P> {
         // Safety: Address is valid, data memory barrier used.
         let status_reg = unsafe { read_volatile(LINE_STATUS_REG) };
         if status_reg & 0b10 != 0 {
-            return Err(hal_nb::nb::Error::Other(Error::Overrun));
+            return Err(hal_nb::nb::Error::Other(overrun()));
         } else if status_reg & 1 != 0 {
             // Safety: As above.
-            return Ok(unsafe { read_volatile(IO_REG) as u8 });
+            let byte = unsafe { read_volatile(IO_REG) as u8 };
+            return self.check_break(byte).map_err(hal_nb::nb::Error::Other);
         }
         Err(hal_nb::nb::Error::WouldBlock)
     }
@@ -166,10 +252,11 @@ impl<P: RxPin> hal_nb::serial::Read for Reader<P> {
 /// This implementation is cancel-safe.
 ///
 /// Because of the way the Mini UART works, using `read` asynchronously will almost always read
-/// only one byte. It is more efficient to use `read_exact` instead.
+/// only one byte. It is more efficient to use `read_exact` instead, or a
+/// [`BufferedReader`](super::BufferedReader).
 impl<P: RxPin> eio_async::Read for Reader<P> {
     fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = Result<usize, Self::Error>> {
-        ReadFut { _reader: self, buf }
+        ReadFut { reader: self, buf }
     }
 
     fn read_exact(
@@ -177,7 +264,7 @@ impl<P: RxPin> eio_async::Read for Reader<P> {
         buf: &mut [u8],
     ) -> impl Future<Output = Result<(), ReadExactError<Self::Error>>> {
         ReadExactFut {
-            _reader: self,
+            reader: self,
             buf_iter: buf.iter_mut(),
         }
     }
@@ -185,7 +272,7 @@ impl<P: RxPin> eio_async::Read for Reader<P> {
 
 #[derive(Debug)]
 pub struct ReadFut<'a, 'b, P> {
-    _reader: &'a mut Reader<P>,
+    reader: &'a mut Reader<P>,
     buf: &'b mut [u8],
 }
 
@@ -197,16 +284,20 @@ impl<P: RxPin> Future for ReadFut<'_, '_, P> {
         cx: &mut core::task::Context,
     ) -> Poll<Self::Output> {
         data_memory_barrier();
-        for (count, byte) in self.buf.iter_mut().enumerate() {
+        let Self { reader, buf } = &mut *self;
+        for (count, byte) in buf.iter_mut().enumerate() {
             // Safety: Address is valid, data memory barrier used.
             let status_reg = unsafe { read_volatile(LINE_STATUS_REG) };
             if status_reg & 0b10 != 0 {
-                return Poll::Ready(Err(Error::Overrun));
+                return Poll::Ready(Err(overrun()));
             }
 
             if status_reg & 1 != 0 {
                 // Safety: As above.
-                *byte = unsafe { read_volatile(IO_REG) as u8 };
+                match reader.check_break(unsafe { read_volatile(IO_REG) as u8 }) {
+                    Ok(received) => *byte = received,
+                    Err(error) => return Poll::Ready(Err(error)),
+                }
             } else if count != 0 {
                 return Poll::Ready(Ok(count));
             } else {
@@ -228,7 +319,7 @@ impl<P: RxPin> Future for ReadFut<'_, '_, P> {
 
 #[derive(Debug)]
 pub struct ReadExactFut<'a, 'b, P> {
-    _reader: &'a mut Reader<P>,
+    reader: &'a mut Reader<P>,
     buf_iter: IterMut<'b, u8>,
 }
 
@@ -240,11 +331,12 @@ impl<P: RxPin> Future for ReadExactFut<'_, '_, P> {
         cx: &mut core::task::Context<'_>,
     ) -> Poll<Self::Output> {
         data_memory_barrier();
-        for byte in self.buf_iter.by_ref() {
+        let Self { reader, buf_iter } = &mut *self;
+        for byte in buf_iter.by_ref() {
             // Safety: Address is valid, data memory barrier used.
             let status_reg = unsafe { read_volatile(LINE_STATUS_REG) };
             if status_reg & 0b10 != 0 {
-                return Poll::Ready(Err(ReadExactError::Other(Error::Overrun)));
+                return Poll::Ready(Err(ReadExactError::Other(overrun())));
             }
 
             if status_reg & 1 == 0 {
@@ -260,7 +352,10 @@ impl<P: RxPin> Future for ReadExactFut<'_, '_, P> {
                 return Poll::Pending;
             }
             // Safety: As above.
-            *byte = unsafe { read_volatile(IO_REG) as u8 };
+            match reader.check_break(unsafe { read_volatile(IO_REG) as u8 }) {
+                Ok(received) => *byte = received,
+                Err(error) => return Poll::Ready(Err(ReadExactError::Other(error))),
+            }
         }
         Poll::Ready(Ok(()))
     }
@@ -282,27 +377,53 @@ pub(super) unsafe fn interrupt_handler() {
 
 /// Trait that represents [`gpio::Pin`]s that are valid for use as the MiniUART RX pin.
 #[allow(private_bounds)]
-pub trait RxPin: Sealed {}
+pub trait RxPin: Sealed + Level {}
 
 // See the BCM2835 manual section 6.2 for the pin mappings.
 impl RxPin for gpio::Pin<15, Alternate5> {}
 impl RxPin for gpio::Pin<33, Alternate5> {}
 impl RxPin for gpio::Pin<41, Alternate5> {}
 
+/// The state of the receiver, see [`Reader::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Status {
+    /// The receiver is not in the middle of a byte.
+    pub idle: bool,
+    /// The number of bytes waiting in the receive FIFO, at most 8.
+    pub fifo_level: u8,
+    /// The number of bytes lost since boot, wrapping around.
+    ///
+    /// The Mini UART does not tell how many bytes an overrun lost, so each overrun counts as one.
+    /// Bytes dropped by a full [`BufferedReader`](super::BufferedReader) are counted exactly.
+    pub dropped: u32,
+}
+
 /// Errors that can occurs when reading from the Mini UART.
+///
+/// The Mini UART does not check parity nor stop bits, so there are no parity or framing errors:
+/// a corrupted byte is received as is.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Error {
+    /// The receive FIFO was full, and at least one byte was lost. See [`Status::dropped`].
     Overrun,
+    /// The line was held low instead of going back to idle, see [`Reader::detect_breaks`].
+    Break,
 }
 
 impl eio::Error for Error {
     fn kind(&self) -> embedded_io::ErrorKind {
-        eio::ErrorKind::Other
+        match self {
+            Error::Overrun => eio::ErrorKind::Other,
+            Error::Break => eio::ErrorKind::InvalidData,
+        }
     }
 }
 
 impl hal_nb::serial::Error for Error {
     fn kind(&self) -> hal_nb::serial::ErrorKind {
-        hal_nb::serial::ErrorKind::Overrun
+        match self {
+            Error::Overrun => hal_nb::serial::ErrorKind::Overrun,
+            Error::Break => hal_nb::serial::ErrorKind::Other,
+        }
     }
 }
@@ -6,6 +6,8 @@ use core::{
     task::{Context, Poll},
 };
 
+use embassy_time::{Duration, Timer};
+
 use crate::{
     aux::uart::registers::*,
     data_memory_barrier, eio, eio_async,
@@ -13,7 +15,7 @@ use crate::{
     hal_nb, set_waker, wake, Sealed, WakerCell, WAKER_CELL_INIT,
 };
 
-use super::Config;
+use super::{BaudRate, BaudRateError, Config};
 
 const FIFO_SIZE: u32 = 8;
 
@@ -47,6 +49,7 @@ impl<P: TxPin> Writer<P> {
         })?;
 
         config.setup();
+        crate::aux::register_interrupt();
 
         Some(Self { _tx_pin: tx_pin })
     }
@@ -71,9 +74,68 @@ impl<P: TxPin> Writer<P> {
         });
 
         config.setup();
+        crate::aux::register_interrupt();
 
         Self { _tx_pin: tx_pin }
     }
+
+    /// Send a break once the pending bytes are sent, by holding the TX line low for `duration`.
+    ///
+    /// The receiver only sees a break if it lasts for more than a byte, so at least 12 bit
+    /// periods.
+    pub fn send_break(&mut self, duration: Duration) {
+        let Ok(()) = eio::Write::flush(self);
+        let _break = Break::start();
+        embassy_time::block_for(duration);
+    }
+
+    /// Like [`Writer::send_break`], but waits for the pending bytes and the break asynchronously.
+    ///
+    /// This is cancel-safe, the break ends when the future is dropped.
+    pub async fn send_break_async(&mut self, duration: Duration) {
+        let Ok(()) = eio_async::Write::flush(self).await;
+        let _break = Break::start();
+        Timer::after(duration).await;
+    }
+
+    /// Recompute the baud rate divisor from the current core clock, and apply it.
+    ///
+    /// Call this after the core clock changed. The divisor is shared with the reader.
+    pub fn refresh_baud_rate(&mut self) -> Result<BaudRate, BaudRateError> {
+        super::refresh_baud_rate()
+    }
+}
+
+/// Holds the TX line low until dropped.
+struct Break;
+
+impl Break {
+    fn start() -> Self {
+        set_break(true);
+        Break
+    }
+}
+
+impl Drop for Break {
+    fn drop(&mut self) {
+        set_break(false);
+    }
+}
+
+fn set_break(enabled: bool) {
+    data_memory_barrier();
+    critical_section::with(|_| {
+        // Safety: Address is valid, memory barrier used.
+        unsafe {
+            let mut reg = read_volatile(LINE_CONTROL_REG);
+            if enabled {
+                reg |= 1 << 6;
+            } else {
+                reg &= !(1 << 6);
+            }
+            write_volatile(LINE_CONTROL_REG, reg);
+        }
+    });
 }
 
 impl<P> Drop for Writer<P> {
@@ -88,7 +150,8 @@ impl<P> Drop for Writer<P> {
                 let control_reg = read_volatile(EXTRA_CONTROL_REG);
                 write_volatile(EXTRA_CONTROL_REG, control_reg & !0b10);
             };
-        })
+        });
+        crate::aux::release_interrupt();
     }
 }
 
@@ -128,7 +191,7 @@ impl<P: TxPin> eio::Write for Writer<P> {
     fn flush(&mut self) -> Result<(), Self::Error> {
         data_memory_barrier();
         // Safety: Address is valid, memory barrier used.
-        while (unsafe { EXTRA_STATUS_REG.read_volatile() } << 8) & 1 == 0 {
+        while (unsafe { EXTRA_STATUS_REG.read_volatile() } >> 9) & 1 == 0 {
             core::hint::spin_loop();
         }
         Ok(())
@@ -159,7 +222,7 @@ impl<P: TxPin> hal_nb::serial::Write for Writer<P> {
     fn flush(&mut self) -> hal_nb::nb::Result<(), Self::Error> {
         data_memory_barrier();
         // Safety: Address is valid, memory barrier used.
-        if (unsafe { EXTRA_STATUS_REG.read_volatile() } << 8) & 1 == 0 {
+        if (unsafe { EXTRA_STATUS_REG.read_volatile() } >> 9) & 1 == 0 {
             return Err(hal_nb::nb::Error::WouldBlock);
         }
         Ok(())
@@ -242,7 +305,7 @@ impl<P: TxPin> Future for FlushFut<'_, P> {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         data_memory_barrier();
         // Safety: Address is valid, memory barrier used.
-        if (unsafe { EXTRA_STATUS_REG.read_volatile() } << 8) & 1 == 0 {
+        if (unsafe { EXTRA_STATUS_REG.read_volatile() } >> 9) & 1 == 0 {
             critical_section::with(|cs| {
                 set_waker(&WRITER_WAKER, cx.waker(), cs);
                 // Safety: As above.
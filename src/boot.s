@@ -13,15 +13,16 @@ _start:
     ldr pc, irq_handler
     ldr pc, fiq_handler
 reset_handler:      .word reset
-undefined_handler:  .word abort_with_panic
+undefined_handler:  .word undefined_instruction
 swi_handler:        .word isr
-prefetch_handler:   .word abort_with_panic
-data_handler:       .word abort_with_panic
+prefetch_handler:   .word prefetch_abort
+data_handler:       .word data_abort
 unused_handler:     .word hang
-irq_handler:        .word isr
-fiq_handler:        .word hang
+irq_handler:        .word irq
+fiq_handler:        .word fiq
 
-// Let's not use r0, r1, r2, for now, I think they hold useful values such as atags, and other stuff.
+// r0, r1 and r2 are given by the firmware: r1 holds the machine type, and r2 the address of the
+// ATAGs or of the device tree. They are left untouched, and handed to `first_stage`.
 reset:
     // Setup the interrupt vector table.
     ldr r3, =__physical_load_address
@@ -31,13 +32,25 @@ reset:
     ldmia r3!,{{r5,r6,r7,r8,r9,r10,r11,r12}}
     stmia r4!,{{r5,r6,r7,r8,r9,r10,r11,r12}}
     
+    // The stacks of the exception modes are in the .bss, the memory below the kernel is left to
+    // the ATAGs or device tree of the firmware.
+
     // Setup stack pointer for ABT mode.
     cps #{ABORT_MODE} // change to abt mode
-    mov sp, #{ABORT_MODE_STACK}
+    ldr sp, =abort_stack_top
     
+    // Setup stack pointer and banked registers for FIQ mode.
+    cps #{FIQ_MODE}
+    ldr sp, =fiq_stack_top
+    mov r8, #0
+    mov r9, #0
+    mov r10, #0
+    mov r11, #0
+    mov r12, #0
+
     // Setup stack pointer for SVC mode.
     cps #{SVC_MODE} // change to svc mode
-    ldr sp, =__physical_load_address
+    ldr sp, =svc_stack_top
     
     // Move the SYSTEM MODE, where the rest of the program will run.
     cpsie aif, #{SYSTEM_MODE}
@@ -55,25 +68,25 @@ zero_bss:
 enable_mmu:
     // Mask for the top 18 bits
     ldr r3, =0x3FFF
-    mvn r0, r3
+    mvn r6, r3
     // Translation table base address
-	ldr	r1, ={TRANSLATION_TABLE}
+	ldr	r7, ={TRANSLATION_TABLE}
 	mov	r4, #0
     // Mask out the bottom 14 bits
-	and	r0, r1, r0
+	and	r6, r7, r6
     // Set the correct flags corresponding to the type of memory the translation table is stored in.
-	orr	r0, r0, #27
+	orr	r6, r6, #27
     // Set the translation table base address with its flags
     // See section B4.9.3
-	mcr	p15, #0, r0, c2, c0, #0
+	mcr	p15, #0, r6, c2, c0, #0
     // Set the value of `N` to 0 in the translation table base control register
     // See section B4.9.3
 	mcr	p15, #0, r4, c2, c0, #2
     // Set all domains to manager mode
     // TODO: figure out how domain work
     // See section B4.9.4
-	mvn	r0, #0
-	mcr	p15, #0, r0, c3, c0, #0
+	mvn	r6, #0
+	mcr	p15, #0, r6, c3, c0, #0
     
     // Invalidate caches and TLB
     // See section B6.6.5
@@ -81,40 +94,116 @@ enable_mmu:
 	mcr	p15, #0, r4, c8, c7, #0
     
     // Enable the MMU on the control register
-	mrc	p15, #0, r0, c1, c0, #0
+	mrc	p15, #0, r6, c1, c0, #0
     // bit 12: enable L1 instruction cache
     // bit 11: enable branch prediction
     // bit 2: enable L1 data cache
     // bit 0: enable MMU
     // See section B3.4.1
-	orr	r0, r0, #5
-	orr	r0, r0, #6144
-	mcr	p15, #0, r0, c1, c0, #0
+	orr	r6, r6, #5
+	orr	r6, r6, #6144
+	mcr	p15, #0, r6, c1, c0, #0
     // data synchronization barrier
 	mcr	p15, #0, r4, c7, c10, #4
 
-    // Call into Rust.
+    // Call into Rust, with r0-r2 as arguments.
     b {FIRST_STAGE}
 
 hang:
     b hang
 
-abort_with_panic:
+// The exception stubs save the registers of the interrupted code as an `ExceptionFrame` on the
+// abort mode stack, and call into Rust with the kind of exception.
+undefined_instruction:
+    // lr points to the instruction after the undefined one.
+    sub lr, lr, #4
+    srsfd #{ABORT_MODE}!
+    cpsid aif, #{ABORT_MODE}
+    // Leave room for the sp and lr of the interrupted mode.
+    sub sp, sp, #8
+    stmfd sp!, {{r0-r12}}
+    mov r0, #{UNDEFINED_INSTRUCTION}
+    b exception
+
+prefetch_abort:
+    // lr points to the instruction after the aborted one.
+    sub lr, lr, #4
+    srsfd #{ABORT_MODE}!
+    cpsid aif, #{ABORT_MODE}
+    sub sp, sp, #8
+    stmfd sp!, {{r0-r12}}
+    mov r0, #{PREFETCH_ABORT}
+    b exception
+
+data_abort:
+    // lr points two instructions after the aborted one.
+    sub lr, lr, #8
+    srsfd #{ABORT_MODE}!
     cpsid aif, #{ABORT_MODE}
-    // Store some register values to have better debugging information in gdb.
-    mrc p15, 0, r0, c5, c0, 0
-    mrc p15, 0, r1, c6, c0, 0
-    b {PANIC}
+    sub sp, sp, #8
+    stmfd sp!, {{r0-r12}}
+    mov r0, #{DATA_ABORT}
+    b exception
+
+exception:
+    // Switch to the interrupted mode to read its sp and lr. User mode cannot be left once entered,
+    // but it shares these registers with system mode.
+    ldr r1, [sp, #64]
+    and r1, r1, #0x1F
+    cmp r1, #0x10
+    moveq r1, #{SYSTEM_MODE}
+    mrs r2, cpsr
+    bic r3, r2, #0x1F
+    orr r3, r3, r1
+    msr cpsr_c, r3
+    mov r4, sp
+    mov r5, lr
+    msr cpsr_c, r2
+    add r1, sp, #52
+    stmia r1, {{r4, r5}}
+    // Call the handler with the frame, keeping the stack 8 byte aligned.
+    mov r1, sp
+    sub sp, sp, #4
+    bl {EXCEPTION}
+    add sp, sp, #4
+    // Resume with the registers of the frame, which the handler may have changed.
+    ldmfd sp!, {{r0-r12}}
+    add sp, sp, #8
+    rfefd sp!
 
+fiq:
+    // lr points to the instruction after the interrupted one.
+    sub lr, lr, #4
+    // r8-r12 are banked, but r12 is saved too to keep the stack 8 byte aligned.
+    stmfd sp!, {{r0-r3, r12, lr}}
+    bl {FIQ}
+    // Return, restoring the cpsr from the spsr.
+    ldmfd sp!, {{r0-r3, r12, pc}}^
+
+irq:
+    // lr points to the instruction after the interrupted one.
+    sub lr, lr, #4
 isr:
-    // Enter the interrupt in SVC mode.
+    // Enter the interrupt in SVC mode, so that nested interrupts do not overwrite the lr and spsr
+    // of this one.
     srsfd #{SVC_MODE}!
     // This is only called from handlers in which the IRQ is disabled, so no need to disable it.
     cpsie af, #{SVC_MODE}
-    // Put all registers on the stack.
-    stmfd sp!, {{r0-r12}}
+    // Put all registers on the stack. lr is the one of SVC mode, which a nested interrupt
+    // overwrites, and it keeps the stack 8 byte aligned.
+    stmfd sp!, {{r0-r12, lr}}
     // ... handle the interrupt ...
     bl interrupt_handler
     // Restore all registers.
-    ldmfd sp!, {{r0-r12}}
+    ldmfd sp!, {{r0-r12, lr}}
     rfefd sp!
+
+// The stacks are zeroed with the rest of the .bss, before any exception can use them.
+.section ".bss.stacks", "aw", %nobits
+.balign 8
+    .space {ABORT_MODE_STACK_SIZE}
+abort_stack_top:
+    .space {FIQ_MODE_STACK_SIZE}
+fiq_stack_top:
+    .space {SVC_MODE_STACK_SIZE}
+svc_stack_top:
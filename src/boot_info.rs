@@ -0,0 +1,226 @@
+//! Information handed to the kernel by the firmware.
+//!
+//! On boot, the firmware leaves the machine type in `r1`, and in `r2` the address of either an
+//! ATAG list or a flattened device tree, depending on its configuration (`disable_commandline_tags`
+//! and `device_tree` in `config.txt`). The runtime reads whichever one it finds, and passes the
+//! result to the `#[main]` function.
+//!
+//! The ATAGs or device tree are read where the firmware left them, below the kernel, so the
+//! command line and the device tree borrow from that memory. The runtime keeps its stacks out of
+//! it, and nothing else writes there.
+
+use core::{ffi::CStr, slice};
+
+/// ATAG list terminator.
+const ATAG_NONE: u32 = 0;
+/// First tag of every ATAG list.
+const ATAG_CORE: u32 = 0x5441_0001;
+/// Describes a region of physical memory.
+const ATAG_MEM: u32 = 0x5441_0002;
+/// The kernel command line.
+const ATAG_CMDLINE: u32 = 0x5441_0009;
+/// Upper bound on the number of tags read, in case the list is corrupted.
+const MAX_TAGS: usize = 64;
+
+/// Magic number at the start of a flattened device tree, stored big-endian.
+const FDT_MAGIC: u32 = 0xD00D_FEED;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// Information about the machine, as given by the firmware.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BootInfo {
+    /// The machine type, as given in `r1` (`0xC42` for the BCM2708 family).
+    pub machine_type: u32,
+    /// The amount of memory available to the ARM core, in bytes.
+    ///
+    /// This is the sum of all memory regions found. `None` if no memory region was described.
+    pub memory_size: Option<u32>,
+    /// The kernel command line, set in `cmdline.txt` and extended by the firmware.
+    pub command_line: Option<&'static str>,
+    /// The flattened device tree blob, if the firmware passed one instead of ATAGs.
+    pub device_tree: Option<&'static [u8]>,
+}
+
+impl BootInfo {
+    /// Read the boot information from the values of `r1` and `r2` at boot.
+    ///
+    /// If `r2` points to neither an ATAG list nor a device tree, only the machine type is set.
+    ///
+    /// # Safety
+    ///
+    /// `r1` and `r2` must be the values given by the firmware, and the memory `r2` points to must
+    /// not have been modified, and must never be modified afterwards.
+    pub unsafe fn from_registers(r1: u32, r2: u32) -> Self {
+        let mut info = BootInfo {
+            machine_type: r1,
+            ..Default::default()
+        };
+        let address = r2 as *const u32;
+        if r2 == 0 || !address.is_aligned() {
+            return info;
+        }
+
+        // Safety: The firmware always places the ATAGs or device tree in RAM, which is mapped, and
+        // at least 8 bytes long. The caller guarantees that `r2` is the value from the firmware.
+        let (first, second) = unsafe { (address.read_volatile(), address.add(1).read_volatile()) };
+        if u32::from_be(first) == FDT_MAGIC {
+            let size = u32::from_be(second) as usize;
+            // Safety: The device tree header says it is `size` bytes long, and the caller
+            // guarantees that it is never modified.
+            let blob = unsafe { slice::from_raw_parts(address as *const u8, size) };
+            info.device_tree = Some(blob);
+            parse_device_tree(blob, &mut info);
+        } else if second == ATAG_CORE {
+            // Safety: There is an ATAG list at `address`, which the caller guarantees is never
+            // modified.
+            unsafe { parse_atags(address, &mut info) };
+        }
+        info
+    }
+}
+
+/// Read the tags we care about from the ATAG list at `address`.
+///
+/// # Safety
+///
+/// `address` must point to a valid ATAG list that is never modified.
+unsafe fn parse_atags(mut address: *const u32, info: &mut BootInfo) {
+    for _ in 0..MAX_TAGS {
+        // Safety: Every tag starts with a two word header: its size in words and its kind.
+        let (size, kind) = unsafe { (*address, *address.add(1)) };
+        if kind == ATAG_NONE || size < 2 {
+            return;
+        }
+        match kind {
+            ATAG_MEM if size >= 4 => {
+                // Safety: The tag is 4 words long: the header, the size and the start address.
+                let region_size = unsafe { *address.add(2) };
+                let total = info.memory_size.unwrap_or(0);
+                info.memory_size = Some(total.saturating_add(region_size));
+            }
+            ATAG_CMDLINE => {
+                // Safety: The tag holds a null terminated string right after its header.
+                let command_line = unsafe { CStr::from_ptr(address.add(2) as *const _) };
+                info.command_line = command_line.to_str().ok();
+            }
+            _ => {}
+        }
+        // Safety: The next tag follows this one, the list ends with an `ATAG_NONE` tag.
+        address = unsafe { address.add(size as usize) };
+    }
+}
+
+/// Read the memory size and the command line from a flattened device tree.
+///
+/// Only the `reg` property of the `/memory` nodes and `bootargs` in `/chosen` are read, anything
+/// malformed is skipped.
+fn parse_device_tree(blob: &'static [u8], info: &mut BootInfo) {
+    let Some(header) = blob.get(..40) else {
+        return;
+    };
+    let field = |offset: usize| read_be(header, offset).unwrap_or(0) as usize;
+    let (Some(structure), Some(strings)) = (
+        blob.get(field(8)..field(8).saturating_add(field(36))),
+        blob.get(field(12)..field(12).saturating_add(field(32))),
+    ) else {
+        return;
+    };
+
+    // The defaults from the devicetree specification, section 2.3.5.
+    let mut address_cells = 2;
+    let mut size_cells = 1;
+    let mut depth = 0usize;
+    // Name of the node we are in, when it is a child of the root.
+    let mut node: &[u8] = &[];
+    let mut offset = 0;
+    while let Some(token) = read_be(structure, offset) {
+        offset += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let Some(name) = structure
+                    .get(offset..)
+                    .and_then(|rest| CStr::from_bytes_until_nul(rest).ok())
+                else {
+                    return;
+                };
+                let name = name.to_bytes();
+                offset += (name.len() + 1).next_multiple_of(4);
+                depth += 1;
+                if depth == 2 {
+                    node = name;
+                }
+            }
+            FDT_END_NODE => depth = depth.saturating_sub(1),
+            FDT_PROP => {
+                let (Some(length), Some(name_offset)) = (
+                    read_be(structure, offset),
+                    offset
+                        .checked_add(4)
+                        .and_then(|offset| read_be(structure, offset)),
+                ) else {
+                    return;
+                };
+                offset += 8;
+                // The length comes from the blob, so a corrupted one must not overflow.
+                let length = length as usize;
+                let (Some(end), Some(next)) = (
+                    offset.checked_add(length),
+                    length
+                        .checked_next_multiple_of(4)
+                        .and_then(|length| offset.checked_add(length)),
+                ) else {
+                    return;
+                };
+                let Some(value) = structure.get(offset..end) else {
+                    return;
+                };
+                offset = next;
+                let Some(name) = strings
+                    .get(name_offset as usize..)
+                    .and_then(|rest| CStr::from_bytes_until_nul(rest).ok())
+                else {
+                    continue;
+                };
+                let name = name.to_bytes();
+
+                if depth == 1 && name == b"#address-cells" {
+                    address_cells = read_be(value, 0).unwrap_or(address_cells);
+                } else if depth == 1 && name == b"#size-cells" {
+                    size_cells = read_be(value, 0).unwrap_or(size_cells);
+                } else if depth == 2 && node == b"chosen" && name == b"bootargs" {
+                    let command_line = CStr::from_bytes_until_nul(value).ok();
+                    info.command_line = command_line.and_then(|s| s.to_str().ok());
+                } else if depth == 2 && is_memory_node(node) && name == b"reg" {
+                    if size_cells == 0 || size_cells > 2 || address_cells > 2 {
+                        continue;
+                    }
+                    let entry_size = (address_cells + size_cells) as usize * 4;
+                    for entry in value.chunks_exact(entry_size) {
+                        // Only the low 32 bits matter, the ARM core cannot address more.
+                        let size = read_be(entry, entry_size - 4).unwrap_or(0);
+                        let total = info.memory_size.unwrap_or(0);
+                        info.memory_size = Some(total.saturating_add(size));
+                    }
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => return,
+            _ => return,
+        }
+    }
+}
+
+/// Whether a node name is `memory` or `memory@<address>`.
+fn is_memory_node(name: &[u8]) -> bool {
+    name == b"memory" || name.starts_with(b"memory@")
+}
+
+/// Read a big-endian `u32` at `offset`.
+fn read_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
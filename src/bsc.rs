@@ -0,0 +1,562 @@
+//! The Broadcom Serial Controllers BSC0 and BSC1, which are I2C masters.
+//!
+//! # Implementation notes
+//! - Adjacent operations of the same kind are sent as a single transfer of the controller. The
+//!   next transfer is started as soon as the previous one is in the FIFO, which makes the
+//!   controller send a repeated start instead of a stop. Like in Linux, this only works when the
+//!   transfer that follows is a read, so reads may only be at the end of a transaction.
+//! - 10-bit addresses are sent as the `0b11110` header followed by the low byte of the address
+//!   as the first written byte, and a header alone before reading.
+//! - The controller does not detect arbitration loss, so it is never reported.
+
+pub mod instance;
+mod registers;
+
+pub use crate::speed::SpeedError;
+
+use core::{
+    future::poll_fn,
+    marker::PhantomData,
+    task::{Poll, Waker},
+};
+
+use crate::{
+    data_memory_barrier,
+    gpio::{
+        self,
+        state::{Alternate0, Alternate1, Alternate2},
+    },
+    hal::{
+        self,
+        i2c::{NoAcknowledgeSource, Operation, SevenBitAddress, TenBitAddress},
+    },
+    hal_async,
+    interrupt::{self, Source},
+    set_waker, speed, wake, Sealed,
+};
+
+use instance::{Instance, One, Registers, Zero};
+use registers::*;
+
+/// A Broadcom Serial Controller, either [`Bsc0`] or [`Bsc1`].
+pub struct Bsc<I: Instance, SDA, SCL> {
+    _sda: SDA,
+    _scl: SCL,
+    _instance: PhantomData<I>,
+}
+
+/// BSC0, on GPIO 0 and 1, 28 and 29, or 44 and 45.
+pub type Bsc0<SDA, SCL> = Bsc<Zero, SDA, SCL>;
+/// BSC1, on GPIO 2 and 3, or 44 and 45.
+pub type Bsc1<SDA, SCL> = Bsc<One, SDA, SCL>;
+
+impl<I: Instance, SDA: SdaPin<I>, SCL: SclPin<I>> Bsc<I, SDA, SCL> {
+    /// Enable the controller and apply `config`.
+    ///
+    /// Returns `None` if the controller is already in use.
+    pub fn get(sda: SDA, scl: SCL, config: &Config) -> Option<Self> {
+        if critical_section::with(|cs| I::taken().borrow(cs).replace(true)) {
+            return None;
+        }
+
+        let divider = config.speed.divider();
+        // These are the delays used by Linux.
+        let falling_edge_delay = (divider / 16).max(1);
+        let rising_edge_delay = (divider / 4).max(1);
+        data_memory_barrier();
+        // Safety: Addresses valid, data memory barrier used, and we have exclusive access. This
+        // also disables the interrupts, and stops the transfer the firmware may have left, as it
+        // uses BSC0 to probe HATs.
+        unsafe {
+            register::<I>(CONTROL).write_volatile(C_ENABLE | C_CLEAR_FIFO);
+            register::<I>(CLOCK_DIVIDER).write_volatile(divider);
+            register::<I>(DATA_DELAY).write_volatile(falling_edge_delay << 16 | rising_edge_delay);
+            register::<I>(CLOCK_STRETCH_TIMEOUT)
+                .write_volatile(config.clock_stretch_timeout as u32);
+            register::<I>(STATUS).write_volatile(S_CLOCK_STRETCH_TIMEOUT | S_ACK_ERROR | S_DONE);
+        }
+        interrupt::register(Source::I2C, interrupt_handler);
+
+        Some(Self {
+            _sda: sda,
+            _scl: scl,
+            _instance: PhantomData,
+        })
+    }
+
+    /// Run a transaction, blocking until it is done.
+    fn transaction_blocking(
+        &mut self,
+        address: Address,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        let mut transfer = Transfer::<I>::start(address, operations)?;
+        loop {
+            if let Poll::Ready(result) = transfer.service() {
+                return result;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Run a transaction, waiting for the interrupts of the controller.
+    async fn transaction_async(
+        &mut self,
+        address: Address,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        let mut transfer = Transfer::<I>::start(address, operations)?;
+        poll_fn(|cx| {
+            let poll = transfer.service();
+            if poll.is_pending() {
+                transfer.listen(cx.waker());
+            }
+            poll
+        })
+        .await
+    }
+}
+
+impl<I: Instance, SDA, SCL> Drop for Bsc<I, SDA, SCL> {
+    fn drop(&mut self) {
+        data_memory_barrier();
+        // Safety: Address valid, data memory barrier used.
+        unsafe { register::<I>(CONTROL).write_volatile(C_CLEAR_FIFO) };
+        critical_section::with(|cs| {
+            I::taken().borrow(cs).set(false);
+            if !Zero::taken().borrow(cs).get() && !One::taken().borrow(cs).get() {
+                interrupt::unregister(Source::I2C);
+            }
+        });
+    }
+}
+
+impl<I: Instance, SDA: SdaPin<I>, SCL: SclPin<I>> hal::i2c::ErrorType for Bsc<I, SDA, SCL> {
+    type Error = Error;
+}
+
+/// A read followed by a write returns [`Error::Unsupported`].
+impl<I: Instance, SDA: SdaPin<I>, SCL: SclPin<I>> hal::i2c::I2c<SevenBitAddress>
+    for Bsc<I, SDA, SCL>
+{
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction_blocking(Address::Seven(address), operations)
+    }
+}
+
+/// A read followed by a write returns [`Error::Unsupported`].
+impl<I: Instance, SDA: SdaPin<I>, SCL: SclPin<I>> hal::i2c::I2c<TenBitAddress>
+    for Bsc<I, SDA, SCL>
+{
+    fn transaction(
+        &mut self,
+        address: TenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction_blocking(Address::Ten(address), operations)
+    }
+}
+
+/// A read followed by a write returns [`Error::Unsupported`]. Dropping the future aborts the
+/// transaction.
+impl<I: Instance, SDA: SdaPin<I>, SCL: SclPin<I>> hal_async::i2c::I2c<SevenBitAddress>
+    for Bsc<I, SDA, SCL>
+{
+    async fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction_async(Address::Seven(address), operations)
+            .await
+    }
+}
+
+/// A read followed by a write returns [`Error::Unsupported`]. Dropping the future aborts the
+/// transaction.
+impl<I: Instance, SDA: SdaPin<I>, SCL: SclPin<I>> hal_async::i2c::I2c<TenBitAddress>
+    for Bsc<I, SDA, SCL>
+{
+    async fn transaction(
+        &mut self,
+        address: TenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.transaction_async(Address::Ten(address), operations)
+            .await
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Address {
+    Seven(SevenBitAddress),
+    Ten(TenBitAddress),
+}
+
+/// The progress of a transaction, shared by the blocking and async implementations.
+///
+/// The transaction is split in segments, which are runs of operations of the same kind, each
+/// sent as one transfer of the controller.
+struct Transfer<'a, 'b, I: Instance> {
+    operations: &'a mut [Operation<'b>],
+    /// The value of the address register.
+    address: u32,
+    /// The low byte of a 10-bit address, still to be written at the start of the first segment.
+    header: Option<u8>,
+    /// The operation and byte of the next byte to go through the FIFO.
+    operation: usize,
+    byte: usize,
+    /// The segment going through the FIFO is a read.
+    reading: bool,
+    /// The operation after the segment going through the FIFO.
+    segment_end: usize,
+    /// All the bytes to write are in the FIFO, and all the segments are started.
+    queued: bool,
+    done: bool,
+    _instance: PhantomData<I>,
+}
+
+impl<'a, 'b, I: Instance> Transfer<'a, 'b, I> {
+    /// Check the transaction, and start its first segment.
+    fn start(address: Address, operations: &'a mut [Operation<'b>]) -> Result<Self, Error> {
+        let mut segments = operations.chunk_by(|a, b| is_read(a) == is_read(b));
+        if segments
+            .by_ref()
+            .skip_while(|ops| !is_read(&ops[0]))
+            .nth(1)
+            .is_some()
+        {
+            return Err(Error::Unsupported);
+        }
+        let too_long = operations
+            .chunk_by(|a, b| is_read(a) == is_read(b))
+            .any(|ops| ops.iter().map(len).sum::<usize>() + 1 > u16::MAX as usize);
+        if too_long {
+            return Err(Error::Unsupported);
+        }
+
+        let (address, header) = match address {
+            Address::Seven(address) => (address as u32 & 0x7F, None),
+            Address::Ten(address) => (
+                0b11110 << 2 | (address as u32 >> 8) & 0b11,
+                Some(address as u8),
+            ),
+        };
+        let mut transfer = Self {
+            operations,
+            address,
+            header,
+            operation: 0,
+            byte: 0,
+            reading: false,
+            segment_end: 0,
+            queued: false,
+            done: false,
+            _instance: PhantomData,
+        };
+
+        data_memory_barrier();
+        // Safety: Addresses valid, data memory barrier used, and the owner of the controller has
+        // exclusive access.
+        unsafe {
+            register::<I>(CONTROL).write_volatile(C_ENABLE | C_CLEAR_FIFO);
+            register::<I>(STATUS).write_volatile(S_CLOCK_STRETCH_TIMEOUT | S_ACK_ERROR | S_DONE);
+        }
+        transfer.start_segment();
+        Ok(transfer)
+    }
+
+    /// Start the transfer of the segment that begins at `segment_end`.
+    fn start_segment(&mut self) {
+        let start = self.segment_end;
+        let reading = self.operations.get(start).is_some_and(is_read);
+        let mut length = 0;
+        if self.header.is_some() {
+            length += 1;
+        }
+        // A 10-bit address must be written before reading.
+        if !(reading && self.header.is_some()) {
+            while let Some(operation) = self.operations.get(self.segment_end) {
+                if is_read(operation) != reading {
+                    break;
+                }
+                length += len(operation);
+                self.segment_end += 1;
+            }
+            self.reading = reading;
+        }
+        self.queued = !self.reading && length == 0 && self.segment_end == self.operations.len();
+
+        // Safety: Addresses valid, the caller used a data memory barrier, and the owner of the
+        // controller has exclusive access.
+        unsafe {
+            register::<I>(SLAVE_ADDRESS).write_volatile(self.address);
+            register::<I>(DATA_LENGTH).write_volatile(length as u32);
+            register::<I>(CONTROL).write_volatile(self.control() | C_START);
+        }
+    }
+
+    /// The control register of the segment going through the FIFO, with interrupts disabled.
+    fn control(&self) -> u32 {
+        if self.reading {
+            C_ENABLE | C_READ
+        } else {
+            C_ENABLE
+        }
+    }
+
+    /// Move as many bytes as possible through the FIFO.
+    fn service(&mut self) -> Poll<Result<(), Error>> {
+        data_memory_barrier();
+        // Safety: Address valid, data memory barrier used.
+        let mut status = unsafe { register::<I>(STATUS).read_volatile() };
+        if status & S_ACK_ERROR != 0 {
+            return Poll::Ready(Err(self.abort(Error::NoAcknowledge)));
+        }
+        if status & S_CLOCK_STRETCH_TIMEOUT != 0 {
+            return Poll::Ready(Err(self.abort(Error::ClockStretchTimeout)));
+        }
+
+        while !self.reading && !self.queued && status & S_TX_DATA != 0 {
+            // The next segment must start before the end of this one, so that the controller sends
+            // a repeated start instead of a stop. Like in Linux, it only starts once the controller
+            // is sending this one, as it would replace it otherwise.
+            let waiting = critical_section::with(|_| match self.next_byte() {
+                Some(byte) => {
+                    // Safety: Address valid, data memory barrier used.
+                    unsafe { register::<I>(FIFO).write_volatile(byte as u32) };
+                    false
+                }
+                None if self.segment_end == self.operations.len() => {
+                    self.queued = true;
+                    false
+                }
+                None if status & S_TRANSFER_ACTIVE != 0 => {
+                    self.start_segment();
+                    false
+                }
+                None => true,
+            });
+            if waiting {
+                break;
+            }
+            // Safety: As above.
+            status = unsafe { register::<I>(STATUS).read_volatile() };
+        }
+        while self.reading && status & S_RX_DATA != 0 {
+            // Safety: Address valid, data memory barrier used.
+            let byte = unsafe { register::<I>(FIFO).read_volatile() as u8 };
+            let Some(slot) = self.next_slot() else {
+                return Poll::Ready(Err(self.abort(Error::Incomplete)));
+            };
+            *slot = byte;
+            // Safety: As above.
+            status = unsafe { register::<I>(STATUS).read_volatile() };
+        }
+
+        if status & S_DONE == 0 {
+            return Poll::Pending;
+        }
+        if !self.reading && !self.queued || self.reading && self.read_pending() {
+            return Poll::Ready(Err(self.abort(Error::Incomplete)));
+        }
+        // Safety: Address valid, data memory barrier used.
+        unsafe { register::<I>(STATUS).write_volatile(S_DONE) };
+        data_memory_barrier();
+        self.done = true;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Enable the interrupts that signal the next step of the transfer.
+    fn listen(&mut self, waker: &Waker) {
+        let interrupts = if self.reading {
+            C_INTERRUPT_DONE | C_INTERRUPT_RX
+        } else if self.queued {
+            C_INTERRUPT_DONE
+        } else {
+            C_INTERRUPT_DONE | C_INTERRUPT_TX
+        };
+        data_memory_barrier();
+        critical_section::with(|cs| {
+            set_waker(I::waker(), waker, cs);
+            // Safety: Address valid, data memory barrier used, and the interrupt handler only
+            // touches this register in a critical section. The start bit is not set again, so
+            // this does not restart the transfer.
+            unsafe { register::<I>(CONTROL).write_volatile(self.control() | interrupts) };
+        });
+    }
+
+    /// Stop the transfer, and clear the FIFO and the errors.
+    fn abort(&mut self, error: Error) -> Error {
+        data_memory_barrier();
+        // Safety: Addresses valid, data memory barrier used.
+        unsafe {
+            register::<I>(CONTROL).write_volatile(C_ENABLE | C_CLEAR_FIFO);
+            register::<I>(STATUS).write_volatile(S_CLOCK_STRETCH_TIMEOUT | S_ACK_ERROR | S_DONE);
+        }
+        data_memory_barrier();
+        self.done = true;
+        error
+    }
+
+    /// The next byte to write in the current segment.
+    fn next_byte(&mut self) -> Option<u8> {
+        if let Some(header) = self.header.take() {
+            return Some(header);
+        }
+        while self.operation < self.segment_end {
+            if let Operation::Write(bytes) = &self.operations[self.operation] {
+                if let Some(&byte) = bytes.get(self.byte) {
+                    self.byte += 1;
+                    return Some(byte);
+                }
+            }
+            self.operation += 1;
+            self.byte = 0;
+        }
+        None
+    }
+
+    /// Whether bytes remain to be read in the current segment.
+    fn read_pending(&self) -> bool {
+        let mut byte = self.byte;
+        self.operations[self.operation..self.segment_end]
+            .iter()
+            .any(|operation| {
+                let pending = len(operation) > byte;
+                byte = 0;
+                pending
+            })
+    }
+
+    /// The slot of the next byte to read in the current segment.
+    fn next_slot(&mut self) -> Option<&mut u8> {
+        while self.operation < self.segment_end {
+            if let Operation::Read(bytes) = &self.operations[self.operation] {
+                if self.byte < bytes.len() {
+                    break;
+                }
+            }
+            self.operation += 1;
+            self.byte = 0;
+        }
+        let Operation::Read(bytes) = self.operations.get_mut(self.operation)? else {
+            return None;
+        };
+        self.byte += 1;
+        bytes.get_mut(self.byte - 1)
+    }
+}
+
+impl<I: Instance> Drop for Transfer<'_, '_, I> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.abort(Error::Incomplete);
+        }
+    }
+}
+
+fn is_read(operation: &Operation<'_>) -> bool {
+    matches!(operation, Operation::Read(_))
+}
+
+fn len(operation: &Operation<'_>) -> usize {
+    match operation {
+        Operation::Read(bytes) => bytes.len(),
+        Operation::Write(bytes) => bytes.len(),
+    }
+}
+
+/// The address of the register at `offset` of the controller `I`.
+fn register<I: Instance>(offset: usize) -> *mut u32 {
+    I::BASE.wrapping_byte_add(offset)
+}
+
+// Handle interrupts that pertain to the controllers.
+pub(crate) fn interrupt_handler() {
+    service_interrupt::<Zero>();
+    service_interrupt::<One>();
+}
+
+fn service_interrupt<I: Instance>() {
+    data_memory_barrier();
+    critical_section::with(|cs| {
+        // Safety: Addresses valid, data memory barrier used.
+        let (control, status) = unsafe {
+            (
+                register::<I>(CONTROL).read_volatile(),
+                register::<I>(STATUS).read_volatile(),
+            )
+        };
+        let fired = control & C_INTERRUPT_TX != 0 && status & S_TX_WRITING != 0
+            || control & C_INTERRUPT_RX != 0 && status & S_RX_READING != 0
+            || control & C_INTERRUPT_DONE != 0 && status & S_DONE != 0
+            || control & C_INTERRUPTS != 0 && status & (S_ACK_ERROR | S_CLOCK_STRETCH_TIMEOUT) != 0;
+        if fired {
+            // Safety: As above.
+            unsafe { register::<I>(CONTROL).write_volatile(control & (C_ENABLE | C_READ)) };
+            wake(I::waker(), cs);
+        }
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub speed: Speed,
+    /// The number of SCL cycles a device may stretch the clock for, or 0 to wait forever.
+    pub clock_stretch_timeout: u16,
+}
+
+/// The frequency of SCL, along with the divider that achieves it.
+pub type Speed = speed::Speed<0xFFFE>;
+
+/// Errors that can occur during a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Error {
+    /// The device did not acknowledge its address or a byte.
+    NoAcknowledge,
+    /// The device stretched the clock for longer than [`Config::clock_stretch_timeout`].
+    ClockStretchTimeout,
+    /// A read is followed by a write, or a run of operations is longer than 65535 bytes.
+    Unsupported,
+    /// The controller ended the transfer early, because the FIFO was not serviced in time.
+    Incomplete,
+}
+
+impl hal::i2c::Error for Error {
+    fn kind(&self) -> hal::i2c::ErrorKind {
+        match self {
+            Error::NoAcknowledge => {
+                hal::i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+            }
+            Error::ClockStretchTimeout => hal::i2c::ErrorKind::Bus,
+            Error::Unsupported | Error::Incomplete => hal::i2c::ErrorKind::Other,
+        }
+    }
+}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the SDA pin of the controller
+/// `I`.
+#[allow(private_bounds)]
+pub trait SdaPin<I: Instance>: Sealed {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the SCL pin of the controller
+/// `I`.
+#[allow(private_bounds)]
+pub trait SclPin<I: Instance>: Sealed {}
+
+// See the BCM2835 manual section 6.2 for the pin mappings.
+impl SdaPin<Zero> for gpio::Pin<0, Alternate0> {}
+impl SclPin<Zero> for gpio::Pin<1, Alternate0> {}
+impl SdaPin<Zero> for gpio::Pin<28, Alternate0> {}
+impl SclPin<Zero> for gpio::Pin<29, Alternate0> {}
+impl SdaPin<Zero> for gpio::Pin<44, Alternate1> {}
+impl SclPin<Zero> for gpio::Pin<45, Alternate1> {}
+impl SdaPin<One> for gpio::Pin<2, Alternate0> {}
+impl SclPin<One> for gpio::Pin<3, Alternate0> {}
+impl SdaPin<One> for gpio::Pin<44, Alternate2> {}
+impl SclPin<One> for gpio::Pin<45, Alternate2> {}
@@ -0,0 +1,59 @@
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use crate::{impl_sealed, Sealed, WakerCell, WAKER_CELL_INIT};
+
+use super::registers::{BSC0, BSC1};
+
+static BSC0_WAKER: WakerCell = WAKER_CELL_INIT;
+static BSC1_WAKER: WakerCell = WAKER_CELL_INIT;
+/// Whether the controllers are owned by a [`Bsc`](super::Bsc).
+static BSC0_TAKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+static BSC1_TAKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// One of the two Broadcom Serial Controllers available to the ARM.
+#[allow(private_bounds)]
+pub trait Instance: Sealed + Registers {}
+
+pub(super) trait Registers {
+    const BASE: *mut u32;
+
+    fn waker() -> &'static WakerCell;
+
+    fn taken() -> &'static Mutex<Cell<bool>>;
+}
+
+/// BSC0, see [`Bsc0`](super::Bsc0).
+pub struct Zero;
+/// BSC1, see [`Bsc1`](super::Bsc1).
+pub struct One;
+
+impl_sealed!(Zero, One);
+
+impl Instance for Zero {}
+impl Instance for One {}
+
+impl Registers for Zero {
+    const BASE: *mut u32 = BSC0;
+
+    fn waker() -> &'static WakerCell {
+        &BSC0_WAKER
+    }
+
+    fn taken() -> &'static Mutex<Cell<bool>> {
+        &BSC0_TAKEN
+    }
+}
+
+impl Registers for One {
+    const BASE: *mut u32 = BSC1;
+
+    fn waker() -> &'static WakerCell {
+        &BSC1_WAKER
+    }
+
+    fn taken() -> &'static Mutex<Cell<bool>> {
+        &BSC1_TAKEN
+    }
+}
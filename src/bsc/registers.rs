@@ -0,0 +1,37 @@
+pub const BSC0: *mut u32 = 0x20205000 as _;
+pub const BSC1: *mut u32 = 0x20804000 as _;
+
+// BCM2835 manual Page 29
+pub const CONTROL: usize = 0x00;
+// BCM2835 manual Page 31
+pub const STATUS: usize = 0x04;
+// BCM2835 manual Page 32
+pub const DATA_LENGTH: usize = 0x08;
+// BCM2835 manual Page 33
+pub const SLAVE_ADDRESS: usize = 0x0C;
+// BCM2835 manual Page 33
+pub const FIFO: usize = 0x10;
+// BCM2835 manual Page 34
+pub const CLOCK_DIVIDER: usize = 0x14;
+// BCM2835 manual Page 34
+pub const DATA_DELAY: usize = 0x18;
+// BCM2835 manual Page 35
+pub const CLOCK_STRETCH_TIMEOUT: usize = 0x1C;
+
+pub const C_READ: u32 = 1;
+pub const C_CLEAR_FIFO: u32 = 0b11 << 4;
+pub const C_START: u32 = 1 << 7;
+pub const C_INTERRUPT_DONE: u32 = 1 << 8;
+pub const C_INTERRUPT_TX: u32 = 1 << 9;
+pub const C_INTERRUPT_RX: u32 = 1 << 10;
+pub const C_INTERRUPTS: u32 = C_INTERRUPT_DONE | C_INTERRUPT_TX | C_INTERRUPT_RX;
+pub const C_ENABLE: u32 = 1 << 15;
+
+pub const S_TRANSFER_ACTIVE: u32 = 1;
+pub const S_DONE: u32 = 1 << 1;
+pub const S_TX_WRITING: u32 = 1 << 2;
+pub const S_RX_READING: u32 = 1 << 3;
+pub const S_TX_DATA: u32 = 1 << 4;
+pub const S_RX_DATA: u32 = 1 << 5;
+pub const S_ACK_ERROR: u32 = 1 << 8;
+pub const S_CLOCK_STRETCH_TIMEOUT: u32 = 1 << 9;
@@ -0,0 +1,429 @@
+//! The BSC/SPI slave, which lets another device drive the Pi as an I2C or SPI peripheral.
+//!
+//! The peripheral only moves bytes between the bus and its 16 entries deep FIFOs: the bytes the
+//! master writes are read with [`eio::Read`], and the bytes it reads are queued with
+//! [`eio::Write`] ahead of time.
+//!
+//! # Implementation notes
+//! - There is no receive timeout interrupt, so the async `read` is only woken once the receive
+//!   FIFO reaches [`Config::rx_fifo_level`]. The default of one eighth is 2 bytes.
+//! - When the master reads while the transmit FIFO is empty, it gets zeros. This is reported as
+//!   an underrun by [`Slave::status`].
+//! - When the master writes while the receive FIFO is full, the byte is lost. The bytes already
+//!   in the FIFO are still read, and the loss is reported as an overrun by [`Slave::status`].
+
+mod registers;
+
+use core::{
+    cell::Cell,
+    convert::Infallible,
+    future::poll_fn,
+    ptr::{read_volatile, write_volatile},
+    task::{Context, Poll},
+};
+
+use critical_section::Mutex;
+
+use crate::{
+    bsc_slave::registers::*,
+    data_memory_barrier, eio, eio_async,
+    gpio::{self, state::Alternate3},
+    hal::{self, i2c::SevenBitAddress},
+    interrupt::{self, Source},
+    serial::FifoLevel,
+    set_waker, wake, Sealed, WakerCell, WAKER_CELL_INIT,
+};
+
+static WAKER: WakerCell = WAKER_CELL_INIT;
+/// Whether the peripheral is owned by a [`Slave`].
+static TAKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// The BSC/SPI slave, either in I2C mode on GPIO 18 (SDA) and 19 (SCL), or in SPI mode on GPIO 18
+/// (MOSI), 19 (SCLK), 20 (MISO) and 21 (CE).
+#[derive(Debug)]
+pub struct Slave<P> {
+    _pins: P,
+}
+
+impl<SDA: SdaPin, SCL: SclPin> Slave<(SDA, SCL)> {
+    /// Enable the slave in I2C mode, answering to `address`.
+    ///
+    /// Returns `None` if the slave is already in use. Panics if `address` is not a 7-bit address.
+    pub fn i2c(sda: SDA, scl: SCL, address: SevenBitAddress, config: &Config) -> Option<Self> {
+        let slave = Self::acquire((sda, scl), CONTROL_I2C, config)?;
+        set_address(address);
+        Some(slave)
+    }
+
+    /// Change the address the slave answers to.
+    ///
+    /// Panics if `address` is not a 7-bit address.
+    pub fn set_address(&mut self, address: SevenBitAddress) {
+        set_address(address);
+    }
+}
+
+impl<MOSI: MosiPin, SCLK: SclkPin, MISO: MisoPin, CS: CsPin> Slave<(MOSI, SCLK, MISO, CS)> {
+    /// Enable the slave in SPI mode, with the clock polarity and phase of `mode`.
+    ///
+    /// Returns `None` if the slave is already in use.
+    pub fn spi(
+        mosi: MOSI,
+        sclk: SCLK,
+        miso: MISO,
+        cs: CS,
+        mode: hal::spi::Mode,
+        config: &Config,
+    ) -> Option<Self> {
+        let mut control = CONTROL_SPI;
+        if mode.polarity == hal::spi::Polarity::IdleHigh {
+            control |= CONTROL_CPOL;
+        }
+        if mode.phase == hal::spi::Phase::CaptureOnSecondTransition {
+            control |= CONTROL_CPHA;
+        }
+        Self::acquire((mosi, sclk, miso, cs), control, config)
+    }
+}
+
+impl<P> Slave<P> {
+    /// Enable the slave with the mode bits in `control`, and apply `config`.
+    ///
+    /// Returns `None` if the slave is already in use.
+    fn acquire(pins: P, control: u32, config: &Config) -> Option<Self> {
+        if critical_section::with(|cs| TAKEN.borrow(cs).replace(true)) {
+            return None;
+        }
+
+        data_memory_barrier();
+        // Safety: Addresses valid, data memory barrier used, and we have exclusive access. The
+        // interrupts a previous owner may have left are disabled, and the break bit clears the
+        // FIFOs.
+        unsafe {
+            write_volatile(INTERRUPT_MASK_REG, 0);
+            write_volatile(INTERRUPT_CLEAR_REG, INTERRUPT_ALL);
+            write_volatile(CONTROL_REG, CONTROL_BREAK);
+            write_volatile(
+                FIFO_LEVEL_REG,
+                (config.rx_fifo_level as u32) << 3 | config.tx_fifo_level as u32,
+            );
+            write_volatile(RECEIVE_STATUS_REG, 0);
+            write_volatile(
+                CONTROL_REG,
+                control | CONTROL_ENABLE | CONTROL_TX_ENABLE | CONTROL_RX_ENABLE,
+            );
+        }
+        interrupt::register(Source::I2C_SPI_SLAVE, interrupt_handler);
+        Some(Self { _pins: pins })
+    }
+
+    /// The state of the FIFOs, and whether the master read from an empty transmit FIFO or wrote
+    /// to a full receive FIFO since the last call.
+    pub fn status(&mut self) -> Status {
+        data_memory_barrier();
+        // Safety: Addresses valid, data memory barrier used. Writing 0 to the error bits clears
+        // them.
+        let (flags, errors) = unsafe {
+            let flags = read_volatile(FLAG_REG);
+            let errors = read_volatile(RECEIVE_STATUS_REG);
+            if errors != 0 {
+                write_volatile(RECEIVE_STATUS_REG, 0);
+            }
+            (flags, errors)
+        };
+        data_memory_barrier();
+        Status {
+            rx_level: (flags >> FLAG_RX_LEVEL_SHIFT & FLAG_LEVEL_MASK) as u8,
+            tx_level: (flags >> FLAG_TX_LEVEL_SHIFT & FLAG_LEVEL_MASK) as u8,
+            rx_busy: flags & FLAG_RX_BUSY != 0,
+            tx_busy: flags & FLAG_TX_BUSY != 0,
+            underrun: errors & STATUS_UNDERRUN != 0,
+            overrun: errors & STATUS_OVERRUN != 0,
+        }
+    }
+}
+
+impl<P> Drop for Slave<P> {
+    fn drop(&mut self) {
+        interrupt::unregister(Source::I2C_SPI_SLAVE);
+        data_memory_barrier();
+        // Safety: Addresses valid, data memory barrier used.
+        unsafe {
+            write_volatile(INTERRUPT_MASK_REG, 0);
+            write_volatile(CONTROL_REG, CONTROL_BREAK);
+            write_volatile(CONTROL_REG, 0);
+        }
+        critical_section::with(|cs| TAKEN.borrow(cs).set(false));
+    }
+}
+
+fn set_address(address: SevenBitAddress) {
+    assert!(address <= 0x7F, "address is not a 7-bit address");
+    data_memory_barrier();
+    // Safety: Address valid, data memory barrier used.
+    unsafe { write_volatile(SLAVE_ADDRESS_REG, address as u32) };
+}
+
+/// Read a byte from the receive FIFO, if it is not empty.
+///
+/// A byte flagged with an overrun is still valid, the lost one is the next. The overrun stays in
+/// the status register for [`Slave::status`] to report.
+fn receive() -> Option<u8> {
+    data_memory_barrier();
+    // Safety: Address is valid, data memory barrier used.
+    if unsafe { read_volatile(FLAG_REG) } & FLAG_RX_EMPTY != 0 {
+        return None;
+    }
+    // Safety: As above.
+    let data = unsafe { read_volatile(DATA_REG) };
+    data_memory_barrier();
+    Some(data as u8)
+}
+
+/// Write bytes of `buf` to the transmit FIFO until it is full.
+///
+/// Returns the number of bytes written.
+fn transmit(buf: &[u8]) -> usize {
+    data_memory_barrier();
+    let mut written = 0;
+    for byte in buf {
+        // Safety: Address is valid, memory barrier used.
+        if unsafe { FLAG_REG.read_volatile() } & FLAG_TX_FULL != 0 {
+            break;
+        }
+        // Safety: As above.
+        unsafe { DATA_REG.write_volatile(*byte as u32) };
+        written += 1;
+    }
+    data_memory_barrier();
+    written
+}
+
+fn flags() -> u32 {
+    data_memory_barrier();
+    // Safety: Address is valid, memory barrier used.
+    let flags = unsafe { FLAG_REG.read_volatile() };
+    data_memory_barrier();
+    flags
+}
+
+/// Wake the current task when `interrupt` triggers.
+///
+/// The interrupts are level triggered, so a FIFO that is already past its level wakes the task
+/// right away.
+fn wait(cx: &Context, interrupt: u32) {
+    data_memory_barrier();
+    critical_section::with(|cs| {
+        set_waker(&WAKER, cx.waker(), cs);
+        // Safety: Addresses valid, data memory barrier used, and the interrupt handler only
+        // touches the mask in a critical section.
+        unsafe {
+            let mask = read_volatile(INTERRUPT_MASK_REG);
+            write_volatile(INTERRUPT_MASK_REG, mask | interrupt);
+        }
+    });
+}
+
+impl<P> eio::ErrorType for Slave<P> {
+    type Error = Infallible;
+}
+
+impl<P> eio::Read for Slave<P> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        for (count, byte) in buf.iter_mut().enumerate() {
+            loop {
+                match receive() {
+                    Some(received) => {
+                        *byte = received;
+                        break;
+                    }
+                    None if count != 0 => return Ok(count),
+                    None => {}
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+}
+
+impl<P> eio::ReadReady for Slave<P> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(flags() & FLAG_RX_EMPTY == 0)
+    }
+}
+
+impl<P> eio::Write for Slave<P> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let written = transmit(buf);
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+
+    /// Wait for the master to read every queued byte.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while flags() & FLAG_TX_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        Ok(())
+    }
+}
+
+impl<P> eio::WriteReady for Slave<P> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(flags() & FLAG_TX_FULL == 0)
+    }
+}
+
+/// This implementation is cancel-safe.
+impl<P> eio_async::Read for Slave<P> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        poll_fn(|cx| {
+            for (count, byte) in buf.iter_mut().enumerate() {
+                match receive() {
+                    Some(received) => *byte = received,
+                    None if count != 0 => return Poll::Ready(Ok(count)),
+                    None => {
+                        wait(cx, INTERRUPT_RX);
+                        return Poll::Pending;
+                    }
+                }
+            }
+            Poll::Ready(Ok(buf.len()))
+        })
+        .await
+    }
+}
+
+/// This implementation is cancel-safe.
+impl<P> eio_async::Write for Slave<P> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        poll_fn(|cx| match transmit(buf) {
+            0 => {
+                wait(cx, INTERRUPT_TX);
+                Poll::Pending
+            }
+            written => Poll::Ready(Ok(written)),
+        })
+        .await
+    }
+
+    /// Wait for the master to read every queued byte.
+    ///
+    /// This is only interrupt driven until the FIFO drains to [`Config::tx_fifo_level`]: the slave
+    /// has no interrupt for an empty FIFO, so the future then wakes itself until the master reads
+    /// the last bytes, which keeps the executor busy. A lower level shortens that.
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        poll_fn(|cx| {
+            data_memory_barrier();
+            // Safety: Address is valid, memory barrier used.
+            let raw_interrupts = unsafe { RAW_INTERRUPT_REG.read_volatile() };
+            if flags() & FLAG_TX_EMPTY != 0 {
+                Poll::Ready(Ok(()))
+            } else if raw_interrupts & INTERRUPT_TX == 0 {
+                wait(cx, INTERRUPT_TX);
+                Poll::Pending
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+// Handle interrupts that pertain to the slave.
+pub(crate) fn interrupt_handler() {
+    data_memory_barrier();
+    critical_section::with(|cs| {
+        // Safety: Addresses valid, data memory barrier used. The interrupts are masked, as the
+        // FIFO levels stay past their threshold until the task runs.
+        unsafe {
+            let interrupts = read_volatile(MASKED_INTERRUPT_REG);
+            let mask = read_volatile(INTERRUPT_MASK_REG);
+            write_volatile(INTERRUPT_MASK_REG, mask & !interrupts);
+            write_volatile(INTERRUPT_CLEAR_REG, interrupts);
+        }
+        wake(&WAKER, cs);
+    });
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Config {
+    /// The receive FIFO level that wakes an async `read`.
+    pub rx_fifo_level: FifoLevel,
+    /// The transmit FIFO level that wakes an async `write` waiting for room.
+    pub tx_fifo_level: FifoLevel,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            rx_fifo_level: FifoLevel::OneEighth,
+            tx_fifo_level: FifoLevel::OneHalf,
+        }
+    }
+}
+
+/// A snapshot of the slave, see [`Slave::status`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Status {
+    /// The number of bytes waiting to be read.
+    pub rx_level: u8,
+    /// The number of bytes waiting for the master to read them.
+    pub tx_level: u8,
+    /// A byte is being received.
+    pub rx_busy: bool,
+    /// A byte is being sent.
+    pub tx_busy: bool,
+    /// The master read from an empty transmit FIFO, and got zeros.
+    pub underrun: bool,
+    /// The master wrote to a full receive FIFO, and a byte was lost.
+    pub overrun: bool,
+}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the I2C SDA pin.
+#[allow(private_bounds)]
+pub trait SdaPin: Sealed {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the I2C SCL pin.
+#[allow(private_bounds)]
+pub trait SclPin: Sealed {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the SPI MOSI pin.
+#[allow(private_bounds)]
+pub trait MosiPin: Sealed {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the SPI SCLK pin.
+#[allow(private_bounds)]
+pub trait SclkPin: Sealed {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the SPI MISO pin.
+#[allow(private_bounds)]
+pub trait MisoPin: Sealed {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the SPI chip enable pin.
+#[allow(private_bounds)]
+pub trait CsPin: Sealed {}
+
+// See the BCM2835 manual section 6.2 for the pin mappings.
+impl SdaPin for gpio::Pin<18, Alternate3> {}
+impl SclPin for gpio::Pin<19, Alternate3> {}
+impl MosiPin for gpio::Pin<18, Alternate3> {}
+impl SclkPin for gpio::Pin<19, Alternate3> {}
+impl MisoPin for gpio::Pin<20, Alternate3> {}
+impl CsPin for gpio::Pin<21, Alternate3> {}
@@ -0,0 +1,59 @@
+/// Data
+/// BCM2835 ARM Peripherals, section 11.2
+pub const DATA_REG: *mut u32 = 0x20214000 as _;
+/// Operation status and error clear
+/// BCM2835 ARM Peripherals, section 11.2
+pub const RECEIVE_STATUS_REG: *mut u32 = 0x20214004 as _;
+/// I2C slave address
+/// BCM2835 ARM Peripherals, section 11.2
+pub const SLAVE_ADDRESS_REG: *mut u32 = 0x20214008 as _;
+/// Control
+/// BCM2835 ARM Peripherals, section 11.2
+pub const CONTROL_REG: *mut u32 = 0x2021400C as _;
+/// Flag
+/// BCM2835 ARM Peripherals, section 11.2
+pub const FLAG_REG: *mut u32 = 0x20214010 as _;
+/// Interrupt FIFO level select
+/// BCM2835 ARM Peripherals, section 11.2
+pub const FIFO_LEVEL_REG: *mut u32 = 0x20214014 as _;
+/// Interrupt mask set/clear
+/// BCM2835 ARM Peripherals, section 11.2
+pub const INTERRUPT_MASK_REG: *mut u32 = 0x20214018 as _;
+/// Raw interrupt status
+/// BCM2835 ARM Peripherals, section 11.2
+pub const RAW_INTERRUPT_REG: *mut u32 = 0x2021401C as _;
+/// Masked interrupt status
+/// BCM2835 ARM Peripherals, section 11.2
+pub const MASKED_INTERRUPT_REG: *mut u32 = 0x20214020 as _;
+/// Interrupt clear
+/// BCM2835 ARM Peripherals, section 11.2
+pub const INTERRUPT_CLEAR_REG: *mut u32 = 0x20214024 as _;
+
+// Receive status register bits.
+pub const STATUS_OVERRUN: u32 = 1;
+pub const STATUS_UNDERRUN: u32 = 1 << 1;
+
+// Control register bits.
+pub const CONTROL_ENABLE: u32 = 1;
+pub const CONTROL_SPI: u32 = 1 << 1;
+pub const CONTROL_I2C: u32 = 1 << 2;
+pub const CONTROL_CPHA: u32 = 1 << 3;
+pub const CONTROL_CPOL: u32 = 1 << 4;
+pub const CONTROL_BREAK: u32 = 1 << 7;
+pub const CONTROL_TX_ENABLE: u32 = 1 << 8;
+pub const CONTROL_RX_ENABLE: u32 = 1 << 9;
+
+// Flag register bits.
+pub const FLAG_TX_BUSY: u32 = 1;
+pub const FLAG_RX_EMPTY: u32 = 1 << 1;
+pub const FLAG_TX_FULL: u32 = 1 << 2;
+pub const FLAG_TX_EMPTY: u32 = 1 << 4;
+pub const FLAG_RX_BUSY: u32 = 1 << 5;
+pub const FLAG_TX_LEVEL_SHIFT: u32 = 6;
+pub const FLAG_RX_LEVEL_SHIFT: u32 = 11;
+pub const FLAG_LEVEL_MASK: u32 = 0x1F;
+
+// Interrupt bits, shared by the mask, status and clear registers.
+pub const INTERRUPT_RX: u32 = 1;
+pub const INTERRUPT_TX: u32 = 1 << 1;
+pub const INTERRUPT_ALL: u32 = 0xF;
@@ -0,0 +1,37 @@
+//! Maintenance of the data cache, for memory shared with the VideoCore and the DMA controller.
+
+use core::arch::asm;
+
+use crate::data_synchronization_barrier;
+
+/// The VideoCore and the DMA controller see the memory of the ARM through its L2 cached alias.
+pub(crate) const BUS_ALIAS: u32 = 0x4000_0000;
+
+pub(crate) const CACHE_LINE: usize = 32;
+
+/// Write back the cache lines of the range to memory, and evict them.
+pub(crate) fn clean_and_invalidate(address: usize, len: usize) {
+    for line in (address & !(CACHE_LINE - 1)..address + len).step_by(CACHE_LINE) {
+        // Safety: The operation is defined in the ARMv6 manual. See section B6.6.5.
+        unsafe {
+            asm!("mcr p15, 0, {}, c7, c14, 1", in(reg) line, options(nostack, preserves_flags))
+        };
+    }
+    data_synchronization_barrier();
+}
+
+/// Evict the cache lines of the range, so that the next reads come from memory.
+///
+/// # Safety
+///
+/// The lines of the range must not hold anything written since they were cleaned, as it is lost.
+pub(crate) unsafe fn invalidate(address: usize, len: usize) {
+    for line in (address & !(CACHE_LINE - 1)..address + len).step_by(CACHE_LINE) {
+        // Safety: The operation is defined in the ARMv6 manual. See section B6.6.5. The caller
+        // ensures that no pending write is lost.
+        unsafe {
+            asm!("mcr p15, 0, {}, c7, c6, 1", in(reg) line, options(nostack, preserves_flags))
+        };
+    }
+    data_synchronization_barrier();
+}
@@ -0,0 +1,178 @@
+//! The DMA controller, used by the drivers to move data between memory and a peripheral.
+//!
+//! Only the channels that the firmware leaves to the ARM are used, and each of them belongs to a
+//! single driver.
+
+mod registers;
+
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::{
+    cache::{self, BUS_ALIAS},
+    data_memory_barrier,
+    dma::registers::*,
+    interrupt::Source,
+};
+
+/// The peripherals are at `0x7E000000` on the bus, and at `0x20000000` for the ARM.
+const PERIPHERAL_BUS_OFFSET: u32 = 0x7E00_0000 - 0x2000_0000;
+
+/// The peripheral that paces a transfer, see section 4.2.1.3 of the BCM2835 manual.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Dreq {
+    SpiTx = 6,
+    SpiRx = 7,
+}
+
+/// A DMA channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Channel(usize);
+
+impl Channel {
+    /// A channel that the firmware leaves to the ARM, and that is not a lite one.
+    ///
+    /// Panics if `number` is not 0, 2, 4 or 5.
+    pub(crate) const fn new(number: usize) -> Self {
+        assert!(
+            matches!(number, 0 | 2 | 4 | 5),
+            "the channel is used by the firmware, or is a lite one"
+        );
+        Self(number)
+    }
+
+    /// The interrupt that the channel raises at the end of a control block that asks for it.
+    pub(crate) const fn interrupt(self) -> Source {
+        Source::gpu(16 + self.0 as u8)
+    }
+
+    fn register(self, offset: usize) -> *mut u32 {
+        (CHANNEL_BASE + self.0 * CHANNEL_STRIDE + offset) as *mut u32
+    }
+
+    /// Enable the channel, and abort what it was doing.
+    pub(crate) fn reset(self) {
+        data_memory_barrier();
+        critical_section::with(|_| {
+            // Safety: Addresses valid, data memory barrier used. The channel belongs to the
+            // caller, and the critical section keeps the other drivers from racing on the global
+            // enable register.
+            unsafe {
+                let enabled = read_volatile(ENABLE_REG);
+                write_volatile(ENABLE_REG, enabled | 1 << self.0);
+                write_volatile(self.register(CONTROL_STATUS), CS_RESET);
+            }
+        });
+    }
+
+    /// Run `control_block`, after clearing the end and interrupt flags.
+    ///
+    /// # Safety
+    ///
+    /// The control block and the memory it points to must stay valid and untouched by the CPU
+    /// until the channel is no longer active, or is reset.
+    pub(crate) unsafe fn start(self, control_block: &ControlBlock) {
+        let address = control_block as *const ControlBlock as usize;
+        cache::clean_and_invalidate(address, size_of::<ControlBlock>());
+        data_memory_barrier();
+        // Safety: Addresses valid, data memory barrier used. The control block was written back
+        // to memory, and is aligned like the controller requires.
+        unsafe {
+            write_volatile(
+                self.register(CONTROL_BLOCK_ADDRESS),
+                address as u32 | BUS_ALIAS,
+            );
+            write_volatile(
+                self.register(CONTROL_STATUS),
+                CS_ACTIVE | CS_END | CS_INTERRUPT,
+            );
+        }
+    }
+
+    /// Whether the channel is still running a control block.
+    pub(crate) fn is_active(self) -> bool {
+        data_memory_barrier();
+        // Safety: Address valid, data memory barrier used.
+        let status = unsafe { read_volatile(self.register(CONTROL_STATUS)) };
+        data_memory_barrier();
+        status & CS_ACTIVE != 0
+    }
+
+    /// Acknowledge the interrupt of the channel.
+    pub(crate) fn clear_interrupt(self) {
+        data_memory_barrier();
+        // Safety: Address valid, data memory barrier used. Writing the active bit as 0 does
+        // nothing once the channel is done, which it is when it raises the interrupt.
+        unsafe { write_volatile(self.register(CONTROL_STATUS), CS_INTERRUPT) };
+    }
+}
+
+/// A transfer for the controller to run, see section 4.2.1.1 of the BCM2835 manual.
+///
+/// The addresses are the ones of the bus. The memory must be identity mapped, which excludes the
+/// stack.
+#[repr(C, align(32))]
+pub(crate) struct ControlBlock {
+    transfer_information: u32,
+    source: u32,
+    destination: u32,
+    len: u32,
+    stride: u32,
+    next: u32,
+    _reserved: [u32; 2],
+}
+
+impl ControlBlock {
+    pub(crate) const EMPTY: Self = Self {
+        transfer_information: 0,
+        source: 0,
+        destination: 0,
+        len: 0,
+        stride: 0,
+        next: 0,
+        _reserved: [0; 2],
+    };
+
+    /// Write the `len` bytes at `source` to the peripheral `register`, as paced by `dreq`.
+    pub(crate) fn to_peripheral(
+        source: *const u8,
+        len: usize,
+        register: *mut u32,
+        dreq: Dreq,
+    ) -> Self {
+        Self {
+            transfer_information: TI_WAIT_RESPONSE
+                | TI_DEST_DREQ
+                | TI_SRC_INCREMENT
+                | (dreq as u32) << TI_PERMAP_SHIFT,
+            source: source as u32 | BUS_ALIAS,
+            destination: register as u32 + PERIPHERAL_BUS_OFFSET,
+            len: len as u32,
+            stride: 0,
+            next: 0,
+            _reserved: [0; 2],
+        }
+    }
+
+    /// Read `len` bytes from the peripheral `register` to `destination`, as paced by `dreq`, and
+    /// raise the interrupt of the channel at the end.
+    pub(crate) fn from_peripheral(
+        register: *mut u32,
+        destination: *mut u8,
+        len: usize,
+        dreq: Dreq,
+    ) -> Self {
+        Self {
+            transfer_information: TI_INTERRUPT_ENABLE
+                | TI_WAIT_RESPONSE
+                | TI_SRC_DREQ
+                | TI_DEST_INCREMENT
+                | (dreq as u32) << TI_PERMAP_SHIFT,
+            source: register as u32 + PERIPHERAL_BUS_OFFSET,
+            destination: destination as u32 | BUS_ALIAS,
+            len: len as u32,
+            stride: 0,
+            next: 0,
+            _reserved: [0; 2],
+        }
+    }
+}
@@ -0,0 +1,26 @@
+/// The registers of channel 0, the next channels follow every `CHANNEL_STRIDE` bytes.
+pub const CHANNEL_BASE: usize = 0x20007000;
+pub const CHANNEL_STRIDE: usize = 0x100;
+
+/// DMA Channel Control and Status
+/// BCM2835 ARM Peripherals, page 47
+pub const CONTROL_STATUS: usize = 0x00;
+/// DMA Channel Control Block Address
+/// BCM2835 ARM Peripherals, page 49
+pub const CONTROL_BLOCK_ADDRESS: usize = 0x04;
+/// DMA Global Enable
+/// BCM2835 ARM Peripherals, section 4.2.1
+pub const ENABLE_REG: *mut u32 = 0x20007FF0 as _;
+
+pub const CS_ACTIVE: u32 = 1;
+pub const CS_END: u32 = 1 << 1;
+pub const CS_INTERRUPT: u32 = 1 << 2;
+pub const CS_RESET: u32 = 1 << 31;
+
+pub const TI_INTERRUPT_ENABLE: u32 = 1;
+pub const TI_WAIT_RESPONSE: u32 = 1 << 3;
+pub const TI_DEST_INCREMENT: u32 = 1 << 4;
+pub const TI_DEST_DREQ: u32 = 1 << 6;
+pub const TI_SRC_INCREMENT: u32 = 1 << 8;
+pub const TI_SRC_DREQ: u32 = 1 << 10;
+pub const TI_PERMAP_SHIFT: u32 = 16;
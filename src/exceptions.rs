@@ -0,0 +1,221 @@
+//! Undefined instruction, prefetch abort and data abort exceptions.
+//!
+//! When one of these exceptions occurs, the registers of the interrupted code are saved in an
+//! [`ExceptionFrame`], the fault status registers are decoded, and the current [`Handler`] is
+//! called on the abort mode stack. The default handler panics with this information, replace it
+//! with [`set_handler`].
+
+use core::{arch::asm, cell::Cell};
+
+use critical_section::Mutex;
+
+/// Kind of exception passed from the vector stubs in `boot.s`.
+pub(crate) const UNDEFINED_INSTRUCTION: u32 = 0;
+pub(crate) const PREFETCH_ABORT: u32 = 1;
+pub(crate) const DATA_ABORT: u32 = 2;
+
+static HANDLER: Mutex<Cell<Handler>> = Mutex::new(Cell::new(default_handler));
+
+/// A function called when an exception occurs.
+///
+/// If the handler returns, execution resumes at `frame.pc` with the registers and status of the
+/// frame. Since `pc` is the faulting instruction, the handler must either fix the cause of the
+/// fault, or move `pc` past it. Changes to `sp` and `lr` are ignored.
+pub type Handler = fn(exception: Exception, frame: &mut ExceptionFrame);
+
+/// Set the function called when an exception occurs.
+pub fn set_handler(handler: Handler) {
+    critical_section::with(|cs| HANDLER.borrow(cs).set(handler));
+}
+
+/// Restore the default handler, which panics.
+pub fn reset_handler() {
+    set_handler(default_handler);
+}
+
+fn default_handler(exception: Exception, frame: &mut ExceptionFrame) {
+    panic!("{exception:?} at {:#010X}\n{frame:#X?}", frame.pc);
+}
+
+/// The registers of the code that caused an exception.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ExceptionFrame {
+    /// General purpose registers `r0` to `r12`.
+    pub r: [u32; 13],
+    /// The stack pointer of the interrupted mode.
+    pub sp: u32,
+    /// The link register of the interrupted mode.
+    pub lr: u32,
+    /// The address of the instruction that caused the exception.
+    pub pc: u32,
+    /// The program status register of the interrupted code.
+    pub cpsr: u32,
+}
+
+/// The exception that occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    /// The instruction at `pc` is not defined, or targets a missing coprocessor.
+    UndefinedInstruction,
+    /// An instruction was fetched from memory that could not be accessed.
+    PrefetchAbort(AbortInfo),
+    /// A load or store accessed memory that could not be accessed.
+    DataAbort(AbortInfo),
+}
+
+/// Information about an abort, from the fault status and fault address registers.
+///
+/// See the ARM1176JZF-S Technical Reference Manual, section 6.9.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbortInfo {
+    /// The address that was accessed.
+    ///
+    /// Not meaningful for imprecise external aborts.
+    pub address: u32,
+    /// The cause of the abort.
+    pub fault: Fault,
+    /// Whether the access was a write. Always `false` for prefetch aborts.
+    pub write: bool,
+    /// The raw fault status register.
+    pub status: u32,
+}
+
+impl AbortInfo {
+    fn new(status: u32, address: u32, write: bool) -> Self {
+        AbortInfo {
+            address,
+            fault: Fault::decode(status),
+            write,
+            status,
+        }
+    }
+}
+
+/// The translation table level at which a fault occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// A first level descriptor, mapping a section.
+    Section,
+    /// A second level descriptor, mapping a page.
+    Page,
+}
+
+/// The cause of an abort, decoded from the fault status register.
+///
+/// See the ARMv6 manual, section B4.6.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The access was not aligned.
+    Alignment,
+    /// An instruction cache maintenance operation faulted.
+    InstructionCacheMaintenance,
+    /// The address is not mapped.
+    Translation(Level),
+    /// The access flag of the descriptor is not set.
+    AccessFlag(Level),
+    /// The domain of the descriptor does not allow accesses.
+    Domain { level: Level, domain: u8 },
+    /// The access permissions of the descriptor do not allow the access.
+    Permission { level: Level, domain: u8 },
+    /// The memory system aborted a translation table walk.
+    ExternalTranslation(Level),
+    /// The memory system aborted the access.
+    External {
+        /// Whether the abort is precise, that is, `pc` is the instruction that caused it.
+        precise: bool,
+    },
+    /// A debug event occurred.
+    Debug,
+    /// A status that is reserved in the ARMv6 architecture.
+    Unknown(u8),
+}
+
+impl Fault {
+    fn decode(status: u32) -> Self {
+        // The status is made of bits [3:0] and bit 10, the domain is bits [7:4].
+        let kind = (status & 0xF) as u8 | ((status >> 6) & 0x10) as u8;
+        let domain = ((status >> 4) & 0xF) as u8;
+        match kind {
+            0b00001 => Fault::Alignment,
+            0b00100 => Fault::InstructionCacheMaintenance,
+            0b01100 => Fault::ExternalTranslation(Level::Section),
+            0b01110 => Fault::ExternalTranslation(Level::Page),
+            0b00101 => Fault::Translation(Level::Section),
+            0b00111 => Fault::Translation(Level::Page),
+            0b00011 => Fault::AccessFlag(Level::Section),
+            0b00110 => Fault::AccessFlag(Level::Page),
+            0b01001 => Fault::Domain {
+                level: Level::Section,
+                domain,
+            },
+            0b01011 => Fault::Domain {
+                level: Level::Page,
+                domain,
+            },
+            0b01101 => Fault::Permission {
+                level: Level::Section,
+                domain,
+            },
+            0b01111 => Fault::Permission {
+                level: Level::Page,
+                domain,
+            },
+            0b01000 => Fault::External { precise: true },
+            0b10110 => Fault::External { precise: false },
+            0b00010 => Fault::Debug,
+            _ => Fault::Unknown(kind),
+        }
+    }
+}
+
+/// Called by the exception vector stubs in `boot.s`, on the abort mode stack.
+///
+/// # Safety
+///
+/// Must only be called by the exception vectors, with a pointer to the saved registers.
+#[cfg_attr(not(feature = "rt"), allow(dead_code))]
+pub(crate) unsafe extern "C" fn handle(kind: u32, frame: *mut ExceptionFrame) {
+    let exception = match kind {
+        UNDEFINED_INSTRUCTION => Exception::UndefinedInstruction,
+        PREFETCH_ABORT => {
+            let status: u32;
+            let address: u32;
+            // Safety: Reading the IFSR and IFAR, see the ARM1176JZF-S TRM, sections 3.2.22 and
+            // 3.2.24.
+            unsafe {
+                asm!(
+                    "mrc p15, 0, {status}, c5, c0, 1",
+                    "mrc p15, 0, {address}, c6, c0, 2",
+                    status = out(reg) status,
+                    address = out(reg) address,
+                    options(nomem, nostack, preserves_flags)
+                )
+            };
+            Exception::PrefetchAbort(AbortInfo::new(status, address, false))
+        }
+        DATA_ABORT => {
+            let status: u32;
+            let address: u32;
+            // Safety: Reading the DFSR and FAR, see the ARM1176JZF-S TRM, sections 3.2.22 and
+            // 3.2.23.
+            unsafe {
+                asm!(
+                    "mrc p15, 0, {status}, c5, c0, 0",
+                    "mrc p15, 0, {address}, c6, c0, 0",
+                    status = out(reg) status,
+                    address = out(reg) address,
+                    options(nomem, nostack, preserves_flags)
+                )
+            };
+            // Bit 11 is set when the abort was caused by a write.
+            Exception::DataAbort(AbortInfo::new(status, address, status & (1 << 11) != 0))
+        }
+        _ => unreachable!("unknown exception kind {kind}"),
+    };
+
+    let handler = critical_section::with(|cs| HANDLER.borrow(cs).get());
+    // Safety: The caller guarantees that the frame points to the saved registers, which nothing
+    // else accesses until the handler returns.
+    handler(exception, unsafe { &mut *frame });
+}
@@ -14,7 +14,11 @@ use embedded_hal::digital::{self, InputPin, OutputPin};
 use embedded_hal_async::digital::Wait;
 use state::{DetectState, Input, Output, PinType, Pull};
 
-use crate::{data_memory_barrier, WakerCell};
+use crate::{
+    data_memory_barrier,
+    interrupt::{self, Source},
+    WakerCell,
+};
 
 const FUNCTION_SELECT_BASE: *mut u32 = 0x20200000 as *mut u32;
 const SET_BASE: *mut u32 = 0x2020001C as *mut u32;
@@ -319,6 +323,11 @@ impl<const PIN: u8> Detector<'_, PIN> {
     }
 
     fn setup_detection(&mut self) {
+        if PIN < 32 {
+            interrupt::register(Source::GPIO_0, interrupt_handler1);
+        } else {
+            interrupt::register(Source::GPIO_1, interrupt_handler2);
+        }
         data_memory_barrier();
         for register in self.state.registers() {
             // Safety: Both the register address and the next one are valid for writing.
@@ -387,9 +396,38 @@ where
     unsafe { f(base.add(offset as usize), mask) }
 }
 
+/// Read the level of a pin, whatever its function.
+pub(crate) trait Level {
+    fn level(&self) -> bool;
+}
+
+impl<const PIN: u8, T> Level for Pin<PIN, T> {
+    fn level(&self) -> bool {
+        data_memory_barrier();
+        // Safety: Both this address and the following one are valid for reading.
+        // Memory barrier used.
+        let high = unsafe {
+            double_register_op::<PIN, _, _>(LEVEL_BASE, |addr, mask| {
+                read_volatile(addr) & mask != 0
+            })
+        };
+        data_memory_barrier();
+        high
+    }
+}
+
 impl<const PIN: u8, T> Drop for Pin<PIN, T> {
     fn drop(&mut self) {
         GPIO_SET.unlock::<PIN>();
+        // Unbind the handler of the bank once none of its pins is in use. The critical section
+        // keeps a detection on another pin from registering it in between.
+        critical_section::with(|_| {
+            if PIN < 32 && GPIO_SET.lower.load(Ordering::Acquire) == 0 {
+                interrupt::unregister(Source::GPIO_0);
+            } else if PIN >= 32 && GPIO_SET.upper.load(Ordering::Acquire) == 0 {
+                interrupt::unregister(Source::GPIO_1);
+            }
+        });
     }
 }
 
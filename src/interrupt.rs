@@ -1,17 +1,150 @@
 use core::{
     arch::asm,
-    sync::atomic::{compiler_fence, Ordering},
+    cell::Cell,
+    sync::atomic::{compiler_fence, AtomicBool, Ordering},
 };
 
+use critical_section::Mutex;
+
 use crate::data_memory_barrier;
 
+pub mod fiq;
+
 const INTERRUPT_BASE: usize = 0x2000_B200;
 const IRQ_BASIC_PENDING: *mut u32 = INTERRUPT_BASE as *mut u32;
 const IRQ_PENDING_1: *mut u32 = (INTERRUPT_BASE + 0x04) as *mut u32;
 const IRQ_PENDING_2: *mut u32 = (INTERRUPT_BASE + 0x08) as *mut u32;
 const ENABLE_IRQS_1: *mut u32 = (INTERRUPT_BASE + 0x10) as *mut u32;
 const ENABLE_IRQS_2: *mut u32 = (INTERRUPT_BASE + 0x14) as *mut u32;
-// const ENABLE_BASIC_IRQS: *mut u32 = (INTERRUPT_BASE + 0x18) as *mut u32;
+const ENABLE_BASIC_IRQS: *mut u32 = (INTERRUPT_BASE + 0x18) as *mut u32;
+const DISABLE_IRQS_1: *mut u32 = (INTERRUPT_BASE + 0x1C) as *mut u32;
+const DISABLE_IRQS_2: *mut u32 = (INTERRUPT_BASE + 0x20) as *mut u32;
+const DISABLE_BASIC_IRQS: *mut u32 = (INTERRUPT_BASE + 0x24) as *mut u32;
+
+/// The registers of the first GPU bank, the second one, and the basic one.
+type Banks = [*mut u32; 3];
+const PENDING: Banks = [IRQ_PENDING_1, IRQ_PENDING_2, IRQ_BASIC_PENDING];
+const ENABLE: Banks = [ENABLE_IRQS_1, ENABLE_IRQS_2, ENABLE_BASIC_IRQS];
+const DISABLE: Banks = [DISABLE_IRQS_1, DISABLE_IRQS_2, DISABLE_BASIC_IRQS];
+
+/// An interrupt source of the BCM2835.
+///
+/// Sources are numbered like in the FIQ control register: the 64 GPU interrupts are 0 to 63, and
+/// the 8 ARM basic interrupts are 64 to 71. See the BCM2835 ARM Peripherals, section 7.5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Source(u8);
+
+impl Source {
+    /// System timer compare 1, used by the time driver.
+    pub const SYSTEM_TIMER_1: Source = Source(1);
+    /// System timer compare 3, used by the time driver.
+    pub const SYSTEM_TIMER_3: Source = Source(3);
+    /// The Mini UART and the two SPI masters of the auxiliary peripherals.
+    pub const AUX: Source = Source(29);
+    /// The BSC/SPI slave.
+    pub const I2C_SPI_SLAVE: Source = Source(43);
+    /// GPIO pins 0 to 31.
+    pub const GPIO_0: Source = Source(49);
+    /// GPIO pins 32 to 53.
+    pub const GPIO_1: Source = Source(50);
+    /// The BSC0 and BSC1 I2C masters.
+    pub const I2C: Source = Source(53);
+    /// The SPI0 master.
+    pub const SPI: Source = Source(54);
+    /// The PL011 UART.
+    pub const UART: Source = Source(57);
+
+    /// The ARM timer.
+    pub const ARM_TIMER: Source = Source::basic(0);
+    /// The ARM mailbox.
+    pub const ARM_MAILBOX: Source = Source::basic(1);
+    /// ARM doorbell 0.
+    pub const ARM_DOORBELL_0: Source = Source::basic(2);
+    /// ARM doorbell 1.
+    pub const ARM_DOORBELL_1: Source = Source::basic(3);
+    /// GPU 0 halted, or GPU 1 halted if bit 10 of the control register 1 is set.
+    pub const GPU_0_HALTED: Source = Source::basic(4);
+    /// GPU 1 halted.
+    pub const GPU_1_HALTED: Source = Source::basic(5);
+    /// Illegal access type 1.
+    pub const ILLEGAL_ACCESS_1: Source = Source::basic(6);
+    /// Illegal access type 0.
+    pub const ILLEGAL_ACCESS_0: Source = Source::basic(7);
+
+    /// The GPU interrupt `number`.
+    ///
+    /// Panics if `number` is not less than 64.
+    pub const fn gpu(number: u8) -> Self {
+        assert!(number < 64, "there are only 64 GPU interrupts");
+        Source(number)
+    }
+
+    /// The ARM basic interrupt `number`.
+    ///
+    /// Panics if `number` is not less than 8.
+    pub const fn basic(number: u8) -> Self {
+        assert!(number < 8, "there are only 8 ARM basic interrupts");
+        Source(64 + number)
+    }
+
+    /// The number of the source, from 0 to 71.
+    pub const fn number(self) -> u8 {
+        self.0
+    }
+
+    /// Unmask the source, so that it triggers the IRQ when it fires.
+    ///
+    /// If a nested handler of a higher or equal priority is running, the source is only unmasked
+    /// once that handler returns.
+    pub fn enable(self) {
+        critical_section::with(|cs| {
+            let enabled = ENABLED.borrow(cs);
+            enabled.set(enabled.get() | self.mask());
+            if MASKED.borrow(cs).get() & self.mask() == 0 {
+                write_sources(ENABLE, self.mask());
+            }
+        });
+    }
+
+    /// Mask the source, so that it no longer triggers the IRQ.
+    pub fn disable(self) {
+        critical_section::with(|cs| {
+            let enabled = ENABLED.borrow(cs);
+            enabled.set(enabled.get() & !self.mask());
+            write_sources(DISABLE, self.mask());
+        });
+    }
+
+    /// Whether the source is asserting its interrupt.
+    pub fn is_pending(self) -> bool {
+        let (register, bit) = self.register(PENDING);
+        data_memory_barrier();
+        // Safety: The registers are defined in the BCM2835 manual. See section 7.5.
+        let pending = unsafe { register.read_volatile() };
+        data_memory_barrier();
+        pending & (1 << bit) != 0
+    }
+
+    /// The bit of the source in a set of sources.
+    const fn mask(self) -> u128 {
+        1 << self.0
+    }
+
+    /// The register of `banks` that holds the source, along with the bit of the source in it.
+    fn register(self, banks: Banks) -> (*mut u32, u8) {
+        (banks[self.0 as usize / 32], self.0 % 32)
+    }
+}
+
+/// Write a set of sources to enable or disable `banks`.
+fn write_sources(banks: Banks, sources: u128) {
+    data_memory_barrier();
+    for (index, register) in banks.into_iter().enumerate() {
+        // Safety: The registers are defined in the BCM2835 manual. See section 7.5. Writing a zero
+        // bit has no effect, so the other sources are left untouched.
+        unsafe { register.write_volatile((sources >> (32 * index)) as u32) };
+    }
+}
 
 /// Enable interrupts.
 ///
@@ -39,63 +172,160 @@ pub unsafe fn enable() {
     compiler_fence(Ordering::SeqCst);
 }
 
-/// Set up interrupt sources in the BCM2835.
+/// A function called when its interrupt source fires.
+///
+/// It runs with interrupts disabled, unless nesting is enabled with [`set_nested`]. It must clear
+/// the cause of the interrupt, otherwise it is called again as soon as it returns.
+pub type Handler = fn();
+
+/// The priority of a handler, higher values preempt lower ones when nesting is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Priority(pub u8);
+
+impl Priority {
+    /// The priority of handlers registered with [`register`], and of sources without a handler.
+    pub const LOWEST: Priority = Priority(0);
+}
+
+type HandlerCell = Mutex<Cell<Option<(Handler, Priority)>>>;
+
+/// The handler of each source and its priority, indexed by source number.
+static HANDLERS: [HandlerCell; 72] = [const { Mutex::new(Cell::new(None)) }; 72];
+/// The sources enabled with `Source::enable`, one bit per source number.
+static ENABLED: Mutex<Cell<u128>> = Mutex::new(Cell::new(0));
+/// The sources masked by the running nested handlers, one bit per source number.
+static MASKED: Mutex<Cell<u128>> = Mutex::new(Cell::new(0));
+static NESTED: AtomicBool = AtomicBool::new(false);
+
+/// Bind `handler` to `source` with the lowest priority, and enable the source.
+///
+/// Returns the handler that was previously bound to the source, if any.
+pub fn register(source: Source, handler: Handler) -> Option<Handler> {
+    register_with_priority(source, Priority::LOWEST, handler)
+}
+
+/// Bind `handler` to `source` with `priority`, and enable the source.
+///
+/// The priority only matters when nesting is enabled with [`set_nested`].
+///
+/// Returns the handler that was previously bound to the source, if any.
+pub fn register_with_priority(
+    source: Source,
+    priority: Priority,
+    handler: Handler,
+) -> Option<Handler> {
+    let previous = critical_section::with(|cs| {
+        HANDLERS[source.number() as usize]
+            .borrow(cs)
+            .replace(Some((handler, priority)))
+    });
+    source.enable();
+    previous.map(|(handler, _)| handler)
+}
+
+/// Disable `source`, and remove its handler.
+///
+/// Returns the handler that was bound to the source, if any.
+pub fn unregister(source: Source) -> Option<Handler> {
+    source.disable();
+    critical_section::with(|cs| HANDLERS[source.number() as usize].borrow(cs).take())
+        .map(|(handler, _)| handler)
+}
+
+/// Enable or disable nested interrupts.
+///
+/// When enabled, handlers run with interrupts enabled, and only the sources of a lower or equal
+/// priority are masked while they run. Handlers of a higher priority can then preempt them. This
+/// stays sound with critical sections, because an interrupt never fires inside of one, and
+/// handlers take their own.
+///
+/// All nested handlers share the 16kb stack of the supervisor mode.
+pub fn set_nested(nested: bool) {
+    NESTED.store(nested, Ordering::Relaxed);
+}
+
+/// Register the handlers of the system timer, used by the time driver.
+///
+/// The other drivers of this crate register their handler when they are acquired, and unregister
+/// it once they are all released.
+#[cfg_attr(not(feature = "rt"), allow(dead_code))]
 pub(crate) fn setup() {
-    data_memory_barrier();
+    // The alarms should not wait for slower handlers when nesting is enabled.
+    register_with_priority(Source::SYSTEM_TIMER_1, Priority(1), || {
+        // Safety: The handler is only bound to the C1 timer interrupt.
+        unsafe { crate::system_time::driver::handler_c1() }
+    });
+    register_with_priority(
+        Source::SYSTEM_TIMER_3,
+        Priority(1),
+        crate::system_time::driver::handler_c3,
+    );
+}
 
-    let mut enable_irqs_1 = 0;
-    let mut enable_irqs_2 = 0;
-    for InterruptDescriptor { bit, .. } in INTERRUPT_SOURCES1 {
-        enable_irqs_1 |= 1 << bit;
-    }
-    for InterruptDescriptor { bit, .. } in INTERRUPT_SOURCES2 {
-        enable_irqs_2 |= 1 << (bit - 32);
-    }
-    // Safety: The register is defined in the BCM2835 manual. See section 7.5.
-    // A data memory barrier is used to ensure that the writes to the registers are not reordered.
-    unsafe {
-        ENABLE_IRQS_1.write_volatile(enable_irqs_1);
-        ENABLE_IRQS_2.write_volatile(enable_irqs_2);
+/// The GPU sources that are also reported in bits 10 to 20 of the basic pending register, in order.
+///
+/// See the BCM2835 ARM Peripherals, page 113.
+const SHORTCUTS: [u8; 11] = [7, 9, 10, 18, 19, 53, 54, 55, 56, 57, 62];
+
+/// Call the handlers of the sources that are set in `pending`, a pending register whose bit 0 is
+/// the source `first`.
+fn dispatch(first: u8, mut pending: u32) {
+    while pending != 0 {
+        let source = Source(first + pending.trailing_zeros() as u8);
+        pending &= pending - 1;
+        let (entry, masked) = critical_section::with(|cs| {
+            (
+                HANDLERS[source.number() as usize].borrow(cs).get(),
+                MASKED.borrow(cs).get() & source.mask() != 0,
+            )
+        });
+        match entry {
+            // A running nested handler has a higher or equal priority.
+            _ if masked => {}
+            // A handler that preempted the previous one might have handled this source already.
+            Some((handler, priority)) if NESTED.load(Ordering::Relaxed) => {
+                if source.is_pending() {
+                    nest(priority, handler);
+                }
+            }
+            Some((handler, _)) => handler(),
+            // Nothing clears this source, so mask it instead of having it fire forever.
+            None => source.disable(),
+        }
     }
 }
 
-struct InterruptDescriptor {
-    // TODO: Make sure that this is less than 64, and support basic interrupts.
-    pub bit: u8,
-    /// # Safety
-    ///
-    /// This function is allowed to be unsafe, but it must be safe to call from the interrupt
-    /// handler.
-    pub handler: unsafe fn(),
-}
+/// Call `handler` with interrupts enabled, and the sources of a lower or equal priority masked.
+fn nest(priority: Priority, handler: Handler) {
+    let (mask, previous) = critical_section::with(|cs| {
+        let mut mask = 0;
+        for (number, slot) in HANDLERS.iter().enumerate() {
+            if slot
+                .borrow(cs)
+                .get()
+                .is_none_or(|(_, other)| other <= priority)
+            {
+                mask |= 1 << number;
+            }
+        }
+        let previous = MASKED.borrow(cs).replace(MASKED.borrow(cs).get() | mask);
+        (mask & !previous, previous)
+    });
+    write_sources(DISABLE, mask);
 
-static INTERRUPT_SOURCES1: &[InterruptDescriptor] = &[
-    // System Timer Interrupts
-    InterruptDescriptor {
-        bit: 1,
-        handler: crate::system_time::driver::handler_c1,
-    },
-    InterruptDescriptor {
-        bit: 3,
-        handler: crate::system_time::driver::handler_c3,
-    },
-    InterruptDescriptor {
-        bit: 29,
-        handler: crate::aux::interrupt_handler,
-    },
-];
-
-static INTERRUPT_SOURCES2: &[InterruptDescriptor] = &[
-    // System Timer Interrupts
-    InterruptDescriptor {
-        bit: 49,
-        handler: crate::gpio::interrupt_handler1,
-    },
-    InterruptDescriptor {
-        bit: 50,
-        handler: crate::gpio::interrupt_handler2,
-    },
-];
+    // Safety: An interrupt never fires inside of a critical section, so the interrupted code is
+    // not in one. The sources that could reenter this handler are masked.
+    unsafe { enable() };
+    handler();
+    // Safety: The handler has left its critical sections.
+    unsafe { disable() };
+
+    critical_section::with(|cs| {
+        MASKED.borrow(cs).set(previous);
+        let enabled = ENABLED.borrow(cs).get();
+        write_sources(ENABLE, mask & enabled);
+    });
+}
 
 /// # Safety
 ///
@@ -126,27 +356,33 @@ unsafe extern "C" fn interrupt_handler() {
     // A data memory barrier is used to ensure that the reads from the registers are not
     // reordered.
     let basic_pending = unsafe { IRQ_BASIC_PENDING.read_volatile() };
+    // The ARM basic interrupts are in the low 8 bits.
+    dispatch(64, basic_pending & 0xFF);
+
+    // The shortcut bits report some GPU sources without having to read their pending register.
+    // They can also be set in the pending registers, so collect everything before dispatching to
+    // call each handler once.
+    let mut pending_1 = 0;
+    let mut pending_2 = 0;
+    for (bit, &source) in SHORTCUTS.iter().enumerate() {
+        if basic_pending & (1 << (10 + bit)) != 0 {
+            match source {
+                0..32 => pending_1 |= 1 << source,
+                _ => pending_2 |= 1 << (source - 32),
+            }
+        }
+    }
     // If IRQ_PENDING_1 has some pending interrupts, handle them.
     if basic_pending & (1 << 8) != 0 {
         // Safety: Same as above.
-        let pending_irqs = unsafe { IRQ_PENDING_1.read_volatile() };
-        for InterruptDescriptor { bit, handler } in INTERRUPT_SOURCES1 {
-            if pending_irqs & (1 << bit) != 0 {
-                // Safety: We are the interrupt handler.
-                unsafe { handler() };
-            }
-        }
+        pending_1 |= unsafe { IRQ_PENDING_1.read_volatile() };
     }
     if basic_pending & (1 << 9) != 0 {
         // Safety: Same as above.
-        let pending_irqs = unsafe { IRQ_PENDING_2.read_volatile() };
-        for InterruptDescriptor { bit, handler } in INTERRUPT_SOURCES2 {
-            if pending_irqs & (1 << (bit - 32)) != 0 {
-                // Safety: We are the interrupt handler.
-                unsafe { handler() };
-            }
-        }
+        pending_2 |= unsafe { IRQ_PENDING_2.read_volatile() };
     }
+    dispatch(0, pending_1);
+    dispatch(32, pending_2);
 
     // Safety: The register is defined in the BCM2835 manual. See section 7.5.
     // A data memory barrier is used to ensure that the reads from the registers are not
@@ -0,0 +1,76 @@
+//! Fast interrupts.
+//!
+//! A single interrupt source can be routed to the FIQ instead of the IRQ. Its handler is called
+//! straight from the FIQ vector, without going through the IRQ dispatch, and it preempts IRQ
+//! handlers and critical sections alike.
+
+use core::{
+    mem,
+    ptr::null_mut,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use super::Source;
+use crate::data_memory_barrier;
+
+/// BCM2835 ARM Peripherals, page 116.
+const FIQ_CONTROL: *mut u32 = 0x2000_B20C as *mut u32;
+const FIQ_ENABLE: u32 = 1 << 7;
+
+/// The handler of the routed source, null when there is none.
+static HANDLER: AtomicPtr<()> = AtomicPtr::new(null_mut());
+
+/// Route `source` to the FIQ, and call `handler` when it fires.
+///
+/// Only one source can be routed at a time, this replaces the previous one. The source is masked
+/// as an IRQ, so that it only triggers the FIQ.
+///
+/// # Safety
+///
+/// The handler runs even inside of critical sections, so it must not access anything that relies
+/// on them for exclusive access, such as a `critical_section::Mutex`. It must clear the source of
+/// the interrupt, otherwise it is called again as soon as it returns.
+pub unsafe fn route(source: Source, handler: fn()) {
+    disable();
+    HANDLER.store(handler as *mut (), Ordering::Release);
+
+    source.disable();
+    // Safety: The register is defined in the BCM2835 manual. See section 7.5. It takes the source
+    // number. `Source::disable` issued the data memory barrier.
+    unsafe { FIQ_CONTROL.write_volatile(FIQ_ENABLE | source.number() as u32) };
+}
+
+/// Stop routing any source to the FIQ.
+///
+/// The source is not enabled back as an IRQ.
+pub fn disable() {
+    data_memory_barrier();
+    // Safety: The register is defined in the BCM2835 manual. See section 7.5.
+    unsafe { FIQ_CONTROL.write_volatile(0) };
+}
+
+/// The source currently routed to the FIQ, if any.
+pub fn source() -> Option<Source> {
+    data_memory_barrier();
+    // Safety: The register is defined in the BCM2835 manual. See section 7.5.
+    let control = unsafe { FIQ_CONTROL.read_volatile() };
+    (control & FIQ_ENABLE != 0).then_some(Source((control & 0x7F) as u8))
+}
+
+/// Called from the FIQ vector in `boot.s`, with FIQs and IRQs disabled.
+///
+/// # Safety
+///
+/// Must only be called by the FIQ vector.
+#[cfg_attr(not(feature = "rt"), allow(dead_code))]
+pub(crate) unsafe extern "C" fn handler() {
+    data_memory_barrier();
+    let handler = HANDLER.load(Ordering::Acquire);
+    if !handler.is_null() {
+        // Safety: The only non-null value stored in `HANDLER` is a `fn()`.
+        let handler = unsafe { mem::transmute::<*mut (), fn()>(handler) };
+        handler();
+    }
+    // We might have interrupted an access to another peripheral.
+    data_memory_barrier();
+}
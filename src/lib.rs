@@ -10,18 +10,29 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 
 pub mod aux;
+pub mod boot_info;
+pub mod bsc;
+pub mod bsc_slave;
+mod cache;
 mod critical_section_impl;
+mod dma;
+pub mod exceptions;
 pub mod executor;
 pub mod gpio;
 pub mod interrupt;
+pub mod mailbox;
 pub mod mmu;
+pub mod pwm;
+#[cfg(feature = "rt")]
+mod rt;
+pub mod serial;
+pub mod shared_bus;
+pub mod speed;
+pub mod spi0;
 pub mod system_time;
+pub mod uart0;
 
-use core::{
-    arch::{asm, global_asm},
-    cell::Cell,
-    task::Waker,
-};
+use core::{arch::asm, cell::Cell, task::Waker};
 
 use ::critical_section::{CriticalSection, Mutex};
 
@@ -32,45 +43,6 @@ pub use embedded_io as eio;
 pub use embedded_io_async as eio_async;
 pub use macros::main;
 
-const ABORT_MODE: u32 = 0b10111;
-const ABORT_MODE_STACK: u32 = 0x4000;
-const SUPERVISOR_MODE: u32 = 0b10011;
-const SYSTEM_MODE: u32 = 0b11111;
-
-global_asm!(
-    include_str!("boot.s"),
-    TRANSLATION_TABLE = sym mmu::TRANSLATION_TABLE,
-    ABORT_MODE = const ABORT_MODE,
-    ABORT_MODE_STACK = const ABORT_MODE_STACK,
-    SVC_MODE = const SUPERVISOR_MODE,
-    SYSTEM_MODE = const SYSTEM_MODE,
-    SYSTEM_MODE_STACK = const mmu::STACK_TOP,
-    PANIC = sym panic,
-    FIRST_STAGE = sym first_stage,
-);
-fn panic() -> ! {
-    panic!()
-}
-
-#[unsafe(no_mangle)]
-extern "C" fn first_stage() -> ! {
-    // Initialize peripherals
-    ::critical_section::with(|cs| {
-        // Safety: The function is called in the first stage of the boot process.
-        unsafe { aux::setup(&cs) };
-    });
-
-    // Enable interrupts
-    interrupt::setup();
-
-    extern "C" {
-        #[link_name = "_main"]
-        fn main() -> !;
-    }
-    // Safety: The main function in defined by the user using the `main!` macro.
-    unsafe { main() };
-}
-
 /// Perform a data memory barrier operation.
 ///
 /// All explicit memory accesses occurring in program order before this operation
@@ -0,0 +1,158 @@
+//! The property channel of the VideoCore mailbox, used to talk to the firmware.
+//!
+//! See the [mailbox property interface](https://github.com/raspberrypi/firmware/wiki/Mailbox-property-interface).
+
+use core::{
+    cell::{Cell, RefCell},
+    mem::size_of_val,
+};
+
+use critical_section::Mutex;
+use embassy_time::{Duration, Instant};
+
+use crate::{
+    cache::{self, BUS_ALIAS},
+    data_memory_barrier,
+};
+
+const MAILBOX_BASE: usize = 0x2000_B880;
+/// Mailbox 0 is written by the VideoCore and read by the ARM.
+const READ: *mut u32 = MAILBOX_BASE as *mut u32;
+const READ_STATUS: *mut u32 = (MAILBOX_BASE + 0x18) as *mut u32;
+/// Mailbox 1 is written by the ARM and read by the VideoCore.
+const WRITE: *mut u32 = (MAILBOX_BASE + 0x20) as *mut u32;
+const WRITE_STATUS: *mut u32 = (MAILBOX_BASE + 0x38) as *mut u32;
+const FULL: u32 = 1 << 31;
+const EMPTY: u32 = 1 << 30;
+
+const PROPERTY_CHANNEL: u32 = 8;
+const REQUEST_SUCCESSFUL: u32 = 0x8000_0000;
+const TAG_RESPONSE: u32 = 1 << 31;
+
+/// How long the firmware has to take and answer a request.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The message sent to the VideoCore, which must be 16 byte aligned.
+///
+/// It is aligned on cache lines so that cleaning it does not touch anything else.
+#[repr(C, align(32))]
+struct Message([u32; 32]);
+
+// The message is in a static, because the stack is not identity mapped.
+static MESSAGE: Mutex<RefCell<Message>> = Mutex::new(RefCell::new(Message([0; 32])));
+/// Whether a request is using `MESSAGE`, which it keeps while waiting for the firmware.
+static BUSY: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// A clock of the SoC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Clock {
+    Emmc = 1,
+    Uart = 2,
+    Arm = 3,
+    /// The VPU clock, which clocks the auxiliary peripherals.
+    Core = 4,
+    V3d = 5,
+    H264 = 6,
+    Isp = 7,
+    Sdram = 8,
+    Pixel = 9,
+    Pwm = 10,
+}
+
+/// The rate of `clock`, in Hz.
+///
+/// Returns `None` if the firmware does not answer, or does not know the clock.
+pub fn clock_rate(clock: Clock) -> Option<u32> {
+    let [_, rate] = property(0x0003_0002, [clock as u32, 0])?;
+    (rate != 0).then_some(rate)
+}
+
+/// Send the property `tag` with `values` as its request, and return the values of its response.
+///
+/// `values` must be large enough to hold the response too. Returns `None` if the firmware does
+/// not answer the request within a second, counting the time spent waiting for another request.
+///
+/// Panics if there are more than 26 values.
+pub fn property<const N: usize>(tag: u32, values: [u32; N]) -> Option<[u32; N]> {
+    assert!(N <= 26, "a property has at most 26 values");
+    let deadline = Instant::now() + TIMEOUT;
+    // The interrupts are only masked while the message is written and read, not while the
+    // firmware answers.
+    let address = loop {
+        let claimed = critical_section::with(|cs| {
+            if BUSY.borrow(cs).replace(true) {
+                return None;
+            }
+            let mut message = MESSAGE.borrow_ref_mut(cs);
+            let words = &mut message.0;
+            words[0] = ((N + 6) * 4) as u32;
+            words[1] = 0;
+            words[2] = tag;
+            words[3] = (N * 4) as u32;
+            words[4] = 0;
+            words[5..5 + N].copy_from_slice(&values);
+            words[5 + N] = 0;
+
+            let address = words.as_ptr() as usize;
+            // The VideoCore does not see the data cache of the ARM.
+            cache::clean_and_invalidate(address, size_of_val(words));
+            Some(address)
+        });
+        match claimed {
+            Some(address) => break address,
+            None if Instant::now() > deadline => return None,
+            None => core::hint::spin_loop(),
+        }
+    };
+
+    let answered = send(address, deadline);
+    critical_section::with(|cs| {
+        BUSY.borrow(cs).set(false);
+        answered?;
+        let message = MESSAGE.borrow_ref(cs);
+        let words = &message.0;
+        // Safety: The range only holds the message, which was cleaned before it was sent.
+        unsafe { cache::invalidate(address, size_of_val(words)) };
+
+        if words[1] != REQUEST_SUCCESSFUL || words[4] & TAG_RESPONSE == 0 {
+            return None;
+        }
+        let mut response = [0; N];
+        response.copy_from_slice(&words[5..5 + N]);
+        Some(response)
+    })
+}
+
+/// Send the message at `address` to the property channel, and wait for the answer.
+///
+/// Returns `None` if the VideoCore does not take or answer it before `deadline`.
+fn send(address: usize, deadline: Instant) -> Option<()> {
+    data_memory_barrier();
+    // Safety: The registers are defined in the mailbox documentation, and a data memory barrier
+    // is used. The message stays claimed until the VideoCore answers, or until the deadline,
+    // after which the VideoCore is assumed to be gone.
+    unsafe {
+        while WRITE_STATUS.read_volatile() & FULL != 0 {
+            if Instant::now() > deadline {
+                data_memory_barrier();
+                return None;
+            }
+            core::hint::spin_loop();
+        }
+        WRITE.write_volatile((address as u32 | BUS_ALIAS) | PROPERTY_CHANNEL);
+        loop {
+            while READ_STATUS.read_volatile() & EMPTY != 0 {
+                if Instant::now() > deadline {
+                    data_memory_barrier();
+                    return None;
+                }
+                core::hint::spin_loop();
+            }
+            if READ.read_volatile() & 0xF == PROPERTY_CHANNEL {
+                break;
+            }
+        }
+    }
+    data_memory_barrier();
+    Some(())
+}
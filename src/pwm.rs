@@ -0,0 +1,393 @@
+//! The PWM controller, with its two channels PWM0 and PWM1.
+//!
+//! Both channels share the PWM clock of the clock manager, and a FIFO. The clock is set by the
+//! first channel that is enabled, so [`Config::clock`] must be the same for both channels.
+//!
+//! # Implementation notes
+//! - [`SetDutyCycle`](hal::pwm::SetDutyCycle) writes the data register, and is scaled to the
+//!   range when the range does not fit in a `u16`. It has no effect on a channel that reads its
+//!   data from the FIFO.
+//! - When both channels use the FIFO, the words written to it go to each channel in turn.
+
+pub mod instance;
+mod registers;
+
+use core::{
+    convert::Infallible,
+    marker::PhantomData,
+    num::NonZeroU32,
+    ptr::{read_volatile, write_volatile},
+};
+
+use crate::{
+    data_memory_barrier,
+    gpio::{
+        self,
+        state::{Alternate0, Alternate5},
+    },
+    hal, Sealed,
+};
+
+use instance::{Instance, One, Registers, Zero};
+use registers::*;
+
+/// A channel of the PWM controller, either [`Pwm0`] or [`Pwm1`].
+#[derive(Debug)]
+pub struct Pwm<I: Instance, P> {
+    _pin: P,
+    range: NonZeroU32,
+    _instance: PhantomData<I>,
+}
+
+/// PWM0, on GPIO 12, 18 or 40.
+pub type Pwm0<P> = Pwm<Zero, P>;
+/// PWM1, on GPIO 13, 19, 41 or 45.
+pub type Pwm1<P> = Pwm<One, P>;
+
+impl<I: Instance, P: PwmPin<I>> Pwm<I, P> {
+    /// Enable the channel and apply `config`, starting the PWM clock if needed.
+    ///
+    /// Returns `None` if the channel is already in use, or if the other channel runs from a
+    /// different clock.
+    pub fn get(pin: P, config: &Config) -> Option<Self> {
+        let mut control = CONTROL_ENABLE;
+        control |= match config.mode {
+            Mode::MarkSpace => CONTROL_MARK_SPACE,
+            Mode::Balanced => 0,
+            Mode::Serializer => CONTROL_SERIALIZER,
+        };
+        if let DataSource::Fifo { repeat_last } = config.source {
+            control |= CONTROL_USE_FIFO;
+            if repeat_last {
+                control |= CONTROL_REPEAT_LAST;
+            }
+        }
+        if config.polarity == Polarity::Inverted {
+            control |= CONTROL_INVERT;
+        }
+        if config.idle_high {
+            control |= CONTROL_SILENCE_HIGH;
+        }
+
+        data_memory_barrier();
+        critical_section::with(|cs| {
+            if I::taken().borrow(cs).get() {
+                return None;
+            }
+            let other_taken = Zero::taken().borrow(cs).get() || One::taken().borrow(cs).get();
+            // Safety: Addresses valid, data memory barrier used. The critical section ensures
+            // that two threads do not race to acquire the channel. The clock is only changed when
+            // the other channel is not in use, and the channel is reset, as the firmware may have
+            // left it enabled for the analog audio output.
+            unsafe {
+                if !config.clock.is_running() {
+                    if other_taken {
+                        return None;
+                    }
+                    config.clock.start();
+                }
+                let mut reg = read_volatile(CONTROL_REG) & !(CONTROL_CHANNEL << I::SHIFT);
+                if !other_taken {
+                    reg |= CONTROL_CLEAR_FIFO;
+                }
+                write_volatile(STATUS_REG, STATUS_ERRORS | I::GAP);
+                write_volatile(I::RANGE, config.range.get());
+                write_volatile(I::DATA, 0);
+                write_volatile(CONTROL_REG, reg | control << I::SHIFT);
+            }
+            I::taken().borrow(cs).set(true);
+            Some(())
+        })?;
+
+        Some(Self {
+            _pin: pin,
+            range: config.range,
+            _instance: PhantomData,
+        })
+    }
+
+    /// Set the length of a period, in PWM clock cycles, or the number of bits sent in serializer
+    /// mode.
+    ///
+    /// The data is not rescaled, and the new range is used from the next period.
+    pub fn set_range(&mut self, range: NonZeroU32) {
+        self.range = range;
+        data_memory_barrier();
+        // Safety: Address valid, data memory barrier used, and only this channel writes it.
+        unsafe { write_volatile(I::RANGE, range.get()) };
+    }
+
+    /// Set the number of high cycles in a period, or the bits to send in serializer mode.
+    pub fn set_data(&mut self, data: u32) {
+        data_memory_barrier();
+        // Safety: Address valid, data memory barrier used, and only this channel writes it.
+        unsafe { write_volatile(I::DATA, data) };
+    }
+
+    /// Write words of `words` to the FIFO until it is full, for a channel that reads from it.
+    ///
+    /// Returns the number of words written.
+    pub fn write_fifo(&mut self, words: &[u32]) -> usize {
+        data_memory_barrier();
+        let mut written = 0;
+        for word in words {
+            // Safety: Addresses valid, data memory barrier used.
+            unsafe {
+                if read_volatile(STATUS_REG) & STATUS_FIFO_FULL != 0 {
+                    break;
+                }
+                write_volatile(FIFO_REG, *word);
+            }
+            written += 1;
+        }
+        data_memory_barrier();
+        written
+    }
+
+    /// Drop the words waiting in the FIFO, including the ones of the other channel.
+    pub fn clear_fifo(&mut self) {
+        data_memory_barrier();
+        critical_section::with(|_| {
+            // Safety: Address valid, data memory barrier used, and the control register is only
+            // modified in a critical section. The clear bit reads as 0.
+            unsafe {
+                let reg = read_volatile(CONTROL_REG);
+                write_volatile(CONTROL_REG, reg | CONTROL_CLEAR_FIFO);
+            }
+        });
+    }
+
+    /// The state of the channel and of the FIFO, clearing the errors.
+    pub fn status(&mut self) -> Status {
+        data_memory_barrier();
+        // Safety: Address valid, data memory barrier used. The error and gap bits are cleared by
+        // writing 1.
+        let status = unsafe {
+            let status = read_volatile(STATUS_REG);
+            write_volatile(STATUS_REG, status & (STATUS_ERRORS | I::GAP));
+            status
+        };
+        data_memory_barrier();
+        Status {
+            transmitting: status & I::STATE != 0,
+            gap: status & I::GAP != 0,
+            fifo_full: status & STATUS_FIFO_FULL != 0,
+            fifo_empty: status & STATUS_FIFO_EMPTY != 0,
+            fifo_write_error: status & STATUS_WRITE_ERROR != 0,
+            fifo_read_error: status & STATUS_READ_ERROR != 0,
+            bus_error: status & STATUS_BUS_ERROR != 0,
+        }
+    }
+}
+
+impl<I: Instance, P> Drop for Pwm<I, P> {
+    fn drop(&mut self) {
+        data_memory_barrier();
+        critical_section::with(|cs| {
+            // Safety: Address valid, data memory barrier used. The clock keeps running, as it may
+            // be shared with the other channel.
+            unsafe {
+                let reg = read_volatile(CONTROL_REG);
+                write_volatile(CONTROL_REG, reg & !(CONTROL_CHANNEL << I::SHIFT));
+            }
+            I::taken().borrow(cs).set(false);
+        });
+    }
+}
+
+impl<I: Instance, P: PwmPin<I>> hal::pwm::ErrorType for Pwm<I, P> {
+    type Error = Infallible;
+}
+
+/// Duty cycles above the maximum are clamped.
+impl<I: Instance, P: PwmPin<I>> hal::pwm::SetDutyCycle for Pwm<I, P> {
+    fn max_duty_cycle(&self) -> u16 {
+        self.range.get().min(u16::MAX as u32) as u16
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        let max = self.max_duty_cycle() as u64;
+        let data = (duty as u64).min(max) * self.range.get() as u64 / max;
+        self.set_data(data as u32);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Config {
+    pub clock: Clock,
+    pub mode: Mode,
+    pub source: DataSource,
+    pub polarity: Polarity,
+    /// The level of the output when the channel has no data to send.
+    pub idle_high: bool,
+    /// The length of a period, in clock cycles, or the number of bits sent in serializer mode.
+    pub range: NonZeroU32,
+}
+
+/// How the data is turned into a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Mode {
+    /// The output is high for `data` cycles, then low for the rest of the range.
+    #[default]
+    MarkSpace,
+    /// The `data` high cycles are spread as evenly as possible over the range.
+    Balanced,
+    /// The `range` most significant bits of the data are sent one per cycle.
+    Serializer,
+}
+
+/// Where the channel reads its data from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum DataSource {
+    /// The data register, set with [`Pwm::set_data`].
+    #[default]
+    Register,
+    /// The FIFO, written with [`Pwm::write_fifo`].
+    Fifo {
+        /// Send the last word again when the FIFO is empty, instead of stopping.
+        repeat_last: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Polarity {
+    #[default]
+    Normal,
+    /// The output is inverted, including its idle level.
+    Inverted,
+}
+
+/// A snapshot of a channel, see [`Pwm::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Status {
+    pub transmitting: bool,
+    /// The channel ran out of data since the last call.
+    pub gap: bool,
+    pub fifo_full: bool,
+    pub fifo_empty: bool,
+    /// A word was written to the FIFO while it was full, and was lost.
+    pub fifo_write_error: bool,
+    /// The FIFO was read while it was empty.
+    pub fifo_read_error: bool,
+    /// A register was written while the FIFO was being written.
+    pub bus_error: bool,
+}
+
+/// The clock manager input of the PWM clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClockSource {
+    /// The 19.2 MHz crystal oscillator.
+    Oscillator = 1,
+    /// PLLD, at 500 MHz.
+    PllD = 6,
+}
+
+impl ClockSource {
+    /// The frequency of the source, in Hz.
+    pub fn frequency(&self) -> u32 {
+        match self {
+            ClockSource::Oscillator => 19_200_000,
+            ClockSource::PllD => 500_000_000,
+        }
+    }
+}
+
+/// The PWM clock, from a source and an integer divider in the range `1..=4095`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Clock {
+    source: ClockSource,
+    divider: u32,
+}
+
+impl Clock {
+    /// The frequency closest to `hz` that can be derived from `source`.
+    pub fn new(source: ClockSource, hz: u32) -> Result<Self, ClockError> {
+        let clock = source.frequency();
+        let divider = match hz {
+            0 => 0,
+            hz => (clock + hz / 2) / hz,
+        };
+        if !(1..=4095).contains(&divider) {
+            return Err(ClockError::OutOfRange {
+                min: clock.div_ceil(4095),
+                max: clock,
+            });
+        }
+
+        Ok(Self { source, divider })
+    }
+
+    /// The frequency that the divider actually achieves, in Hz.
+    pub fn frequency(&self) -> u32 {
+        self.source.frequency() / self.divider
+    }
+
+    pub fn source(&self) -> ClockSource {
+        self.source
+    }
+
+    /// Whether the PWM clock runs with this source and divider.
+    ///
+    /// # Safety
+    ///
+    /// A data memory barrier must be used before.
+    unsafe fn is_running(&self) -> bool {
+        // Safety: Addresses valid, the caller used a data memory barrier.
+        let (control, divisor) = unsafe {
+            (
+                read_volatile(CLOCK_CONTROL_REG),
+                read_volatile(CLOCK_DIVISOR_REG),
+            )
+        };
+        data_memory_barrier();
+        control & CLOCK_ENABLE != 0
+            && control & CLOCK_SOURCE_MASK == self.source as u32
+            && divisor == self.divider << CLOCK_INTEGER_SHIFT
+    }
+
+    /// Stop the PWM clock, and start it with this source and divider.
+    ///
+    /// # Safety
+    ///
+    /// Both channels must be disabled, and a data memory barrier must be used before.
+    unsafe fn start(&self) {
+        let source = self.source as u32;
+        // Safety: Addresses valid, the caller used a data memory barrier. The divisor must only be
+        // changed once the clock is no longer busy.
+        unsafe {
+            let control = read_volatile(CLOCK_CONTROL_REG) & CLOCK_SOURCE_MASK;
+            write_volatile(CLOCK_CONTROL_REG, CLOCK_PASSWORD | control);
+            while read_volatile(CLOCK_CONTROL_REG) & CLOCK_BUSY != 0 {
+                core::hint::spin_loop();
+            }
+            write_volatile(
+                CLOCK_DIVISOR_REG,
+                CLOCK_PASSWORD | self.divider << CLOCK_INTEGER_SHIFT,
+            );
+            write_volatile(CLOCK_CONTROL_REG, CLOCK_PASSWORD | source);
+            write_volatile(CLOCK_CONTROL_REG, CLOCK_PASSWORD | source | CLOCK_ENABLE);
+        }
+        data_memory_barrier();
+    }
+}
+
+/// The reasons a clock frequency cannot be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClockError {
+    /// The frequency is not reachable from the source.
+    OutOfRange { min: u32, max: u32 },
+}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the output of the channel `I`.
+#[allow(private_bounds)]
+pub trait PwmPin<I: Instance>: Sealed {}
+
+// See the BCM2835 manual section 6.2 for the pin mappings.
+impl PwmPin<Zero> for gpio::Pin<12, Alternate0> {}
+impl PwmPin<One> for gpio::Pin<13, Alternate0> {}
+impl PwmPin<Zero> for gpio::Pin<18, Alternate5> {}
+impl PwmPin<One> for gpio::Pin<19, Alternate5> {}
+impl PwmPin<Zero> for gpio::Pin<40, Alternate0> {}
+impl PwmPin<One> for gpio::Pin<41, Alternate0> {}
+impl PwmPin<One> for gpio::Pin<45, Alternate0> {}
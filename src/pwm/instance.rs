@@ -0,0 +1,62 @@
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use crate::{impl_sealed, Sealed};
+
+use super::registers::{DATA_1_REG, DATA_2_REG, RANGE_1_REG, RANGE_2_REG};
+
+/// Whether the channels are owned by a [`Pwm`](super::Pwm).
+static PWM0_TAKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+static PWM1_TAKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// One of the two PWM channels.
+#[allow(private_bounds)]
+pub trait Instance: Sealed + Registers {}
+
+pub(super) trait Registers {
+    /// The shift of the bits of the channel in the control register.
+    const SHIFT: u32;
+    /// The status bit set when the channel has no more data to send.
+    const GAP: u32;
+    /// The status bit set while the channel is transmitting.
+    const STATE: u32;
+    const RANGE: *mut u32;
+    const DATA: *mut u32;
+
+    fn taken() -> &'static Mutex<Cell<bool>>;
+}
+
+/// PWM0, channel 1 of the controller, see [`Pwm0`](super::Pwm0).
+pub struct Zero;
+/// PWM1, channel 2 of the controller, see [`Pwm1`](super::Pwm1).
+pub struct One;
+
+impl_sealed!(Zero, One);
+
+impl Instance for Zero {}
+impl Instance for One {}
+
+impl Registers for Zero {
+    const SHIFT: u32 = 0;
+    const GAP: u32 = 1 << 4;
+    const STATE: u32 = 1 << 9;
+    const RANGE: *mut u32 = RANGE_1_REG;
+    const DATA: *mut u32 = DATA_1_REG;
+
+    fn taken() -> &'static Mutex<Cell<bool>> {
+        &PWM0_TAKEN
+    }
+}
+
+impl Registers for One {
+    const SHIFT: u32 = 8;
+    const GAP: u32 = 1 << 5;
+    const STATE: u32 = 1 << 10;
+    const RANGE: *mut u32 = RANGE_2_REG;
+    const DATA: *mut u32 = DATA_2_REG;
+
+    fn taken() -> &'static Mutex<Cell<bool>> {
+        &PWM1_TAKEN
+    }
+}
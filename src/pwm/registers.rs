@@ -0,0 +1,55 @@
+/// PWM Control
+/// BCM2835 ARM Peripherals, page 142
+pub const CONTROL_REG: *mut u32 = 0x2020C000 as _;
+/// PWM Status
+/// BCM2835 ARM Peripherals, page 144
+pub const STATUS_REG: *mut u32 = 0x2020C004 as _;
+/// PWM Channel 1 Range
+/// BCM2835 ARM Peripherals, page 145
+pub const RANGE_1_REG: *mut u32 = 0x2020C010 as _;
+/// PWM Channel 1 Data
+/// BCM2835 ARM Peripherals, page 145
+pub const DATA_1_REG: *mut u32 = 0x2020C014 as _;
+/// PWM FIFO Input
+/// BCM2835 ARM Peripherals, page 146
+pub const FIFO_REG: *mut u32 = 0x2020C018 as _;
+/// PWM Channel 2 Range
+/// BCM2835 ARM Peripherals, page 146
+pub const RANGE_2_REG: *mut u32 = 0x2020C020 as _;
+/// PWM Channel 2 Data
+/// BCM2835 ARM Peripherals, page 147
+pub const DATA_2_REG: *mut u32 = 0x2020C024 as _;
+
+/// Clock Manager PWM Clock Control
+/// Not listed in the BCM2835 ARM Peripherals, see section 6.3 for the layout
+pub const CLOCK_CONTROL_REG: *mut u32 = 0x201010A0 as _;
+/// Clock Manager PWM Clock Divisor
+/// Not listed in the BCM2835 ARM Peripherals, see section 6.3 for the layout
+pub const CLOCK_DIVISOR_REG: *mut u32 = 0x201010A4 as _;
+
+// Control register bits of channel 1, shifted by `Registers::SHIFT` for channel 2.
+pub const CONTROL_ENABLE: u32 = 1;
+pub const CONTROL_SERIALIZER: u32 = 1 << 1;
+pub const CONTROL_REPEAT_LAST: u32 = 1 << 2;
+pub const CONTROL_SILENCE_HIGH: u32 = 1 << 3;
+pub const CONTROL_INVERT: u32 = 1 << 4;
+pub const CONTROL_USE_FIFO: u32 = 1 << 5;
+pub const CONTROL_MARK_SPACE: u32 = 1 << 7;
+pub const CONTROL_CHANNEL: u32 = 0xFF;
+// Shared by both channels.
+pub const CONTROL_CLEAR_FIFO: u32 = 1 << 6;
+
+// Status register bits.
+pub const STATUS_FIFO_FULL: u32 = 1;
+pub const STATUS_FIFO_EMPTY: u32 = 1 << 1;
+pub const STATUS_WRITE_ERROR: u32 = 1 << 2;
+pub const STATUS_READ_ERROR: u32 = 1 << 3;
+pub const STATUS_BUS_ERROR: u32 = 1 << 8;
+pub const STATUS_ERRORS: u32 = STATUS_WRITE_ERROR | STATUS_READ_ERROR | STATUS_BUS_ERROR;
+
+// Clock manager bits.
+pub const CLOCK_PASSWORD: u32 = 0x5A << 24;
+pub const CLOCK_ENABLE: u32 = 1 << 4;
+pub const CLOCK_BUSY: u32 = 1 << 7;
+pub const CLOCK_SOURCE_MASK: u32 = 0xF;
+pub const CLOCK_INTEGER_SHIFT: u32 = 12;
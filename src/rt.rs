@@ -0,0 +1,64 @@
+//! The runtime: boot code, exception vectors and the first stage before the user's `main`.
+
+use core::{arch::global_asm, mem::MaybeUninit};
+
+use crate::{aux, boot_info::BootInfo, exceptions, interrupt, mmu, uart0};
+
+const ABORT_MODE: u32 = 0b10111;
+const ABORT_MODE_STACK_SIZE: u32 = 0x2000;
+const FIQ_MODE: u32 = 0b10001;
+const FIQ_MODE_STACK_SIZE: u32 = 0x1000;
+const SUPERVISOR_MODE: u32 = 0b10011;
+const SUPERVISOR_MODE_STACK_SIZE: u32 = 0x4000;
+const SYSTEM_MODE: u32 = 0b11111;
+
+/// The boot information, which `main` borrows for the rest of the program.
+static mut BOOT_INFO: MaybeUninit<BootInfo> = MaybeUninit::uninit();
+
+global_asm!(
+    include_str!("boot.s"),
+    TRANSLATION_TABLE = sym mmu::TRANSLATION_TABLE,
+    ABORT_MODE = const ABORT_MODE,
+    ABORT_MODE_STACK_SIZE = const ABORT_MODE_STACK_SIZE,
+    FIQ_MODE = const FIQ_MODE,
+    FIQ_MODE_STACK_SIZE = const FIQ_MODE_STACK_SIZE,
+    SVC_MODE = const SUPERVISOR_MODE,
+    SVC_MODE_STACK_SIZE = const SUPERVISOR_MODE_STACK_SIZE,
+    SYSTEM_MODE = const SYSTEM_MODE,
+    SYSTEM_MODE_STACK = const mmu::STACK_TOP,
+    UNDEFINED_INSTRUCTION = const exceptions::UNDEFINED_INSTRUCTION,
+    PREFETCH_ABORT = const exceptions::PREFETCH_ABORT,
+    DATA_ABORT = const exceptions::DATA_ABORT,
+    EXCEPTION = sym exceptions::handle,
+    FIQ = sym interrupt::fiq::handler,
+    FIRST_STAGE = sym first_stage,
+);
+
+#[unsafe(no_mangle)]
+extern "C" fn first_stage(_r0: u32, r1: u32, r2: u32) -> ! {
+    let boot_info = &raw mut BOOT_INFO;
+    // Safety: The registers are passed untouched from the firmware. The ATAGs or device tree sit
+    // below the kernel, where nothing but the exception vectors is written. `first_stage` runs
+    // once, so this is the only reference to `BOOT_INFO`.
+    let boot_info: &'static BootInfo =
+        unsafe { (*boot_info).write(BootInfo::from_registers(r1, r2)) };
+
+    // Initialize peripherals
+    ::critical_section::with(|cs| {
+        // Safety: The function is called in the first stage of the boot process.
+        unsafe { aux::setup(&cs) };
+        // Safety: Same as above.
+        unsafe { uart0::setup(&cs) };
+    });
+
+    // Enable interrupts
+    interrupt::setup();
+
+    extern "Rust" {
+        #[link_name = "_main"]
+        fn main(boot_info: &'static BootInfo) -> !;
+    }
+    // Safety: The main function in defined by the user using the `main!` macro, which always
+    // exports it with this signature.
+    unsafe { main(boot_info) };
+}
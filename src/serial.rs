@@ -0,0 +1,43 @@
+//! The settings shared by the serial peripherals.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum WordLength {
+    FiveBits = 0,
+    SixBits = 1,
+    SevenBits = 2,
+    #[default]
+    EightBits = 3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Parity {
+    #[default]
+    None,
+    Even,
+    Odd,
+    /// The parity bit is always 1.
+    One,
+    /// The parity bit is always 0.
+    Zero,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum StopBits {
+    #[default]
+    One,
+    Two,
+}
+
+/// The FIFO level at which an interrupt is triggered.
+///
+/// The receive interrupt triggers when the receive FIFO fills up to the level, and the transmit
+/// interrupt when the transmit FIFO drains down to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum FifoLevel {
+    OneEighth = 0,
+    OneQuarter = 1,
+    #[default]
+    OneHalf = 2,
+    ThreeQuarters = 3,
+    SevenEighths = 4,
+}
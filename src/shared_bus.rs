@@ -0,0 +1,116 @@
+//! A bus shared by the devices on it, which borrow it for the duration of a transaction.
+
+use core::{
+    cell::{RefCell, RefMut},
+    future::poll_fn,
+    ops::{Deref, DerefMut},
+    task::{Poll, Waker},
+};
+
+use critical_section::{CriticalSection, Mutex};
+
+/// The number of tasks that can wait for the bus without being woken spuriously.
+const WAITERS: usize = 4;
+
+/// A bus shared by several devices.
+///
+/// Blocking transactions panic if the bus is already borrowed, while async ones wait for it to be
+/// released.
+pub struct SharedBus<T> {
+    bus: RefCell<T>,
+    waiters: Mutex<RefCell<[Option<Waker>; WAITERS]>>,
+}
+
+impl<T> SharedBus<T> {
+    pub const fn new(bus: T) -> Self {
+        Self {
+            bus: RefCell::new(bus),
+            waiters: Mutex::new(RefCell::new([const { None }; WAITERS])),
+        }
+    }
+
+    /// Give back the bus.
+    pub fn into_inner(self) -> T {
+        self.bus.into_inner()
+    }
+
+    /// Borrow the bus.
+    ///
+    /// Panics if it is already borrowed.
+    pub(crate) fn borrow(&self) -> Borrow<'_, T> {
+        Borrow {
+            bus: self.bus.borrow_mut(),
+            shared: self,
+        }
+    }
+
+    /// Borrow the bus once the device that holds it releases it.
+    pub(crate) async fn wait_borrow(&self) -> Borrow<'_, T> {
+        poll_fn(|cx| {
+            critical_section::with(|cs| match self.bus.try_borrow_mut() {
+                Ok(bus) => Poll::Ready(Borrow { bus, shared: self }),
+                Err(_) => {
+                    self.add_waiter(cx.waker(), cs);
+                    Poll::Pending
+                }
+            })
+        })
+        .await
+    }
+
+    /// Remember `waker`, to wake it once the bus is released.
+    ///
+    /// If there is no room left, the waiters are woken so that they register again.
+    fn add_waiter(&self, waker: &Waker, cs: CriticalSection) {
+        let mut waiters = self.waiters.borrow_ref_mut(cs);
+        if waiters.iter().flatten().any(|w| w.will_wake(waker)) {
+            return;
+        }
+        let slot = match waiters.iter_mut().find(|w| w.is_none()) {
+            Some(slot) => slot,
+            None => {
+                waiters
+                    .iter_mut()
+                    .flat_map(Option::take)
+                    .for_each(Waker::wake);
+                &mut waiters[0]
+            }
+        };
+        *slot = Some(waker.clone());
+    }
+}
+
+/// The bus, borrowed by a device.
+///
+/// The waiting devices are woken when it is dropped.
+pub(crate) struct Borrow<'a, T> {
+    bus: RefMut<'a, T>,
+    shared: &'a SharedBus<T>,
+}
+
+impl<T> Deref for Borrow<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.bus
+    }
+}
+
+impl<T> DerefMut for Borrow<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.bus
+    }
+}
+
+impl<T> Drop for Borrow<'_, T> {
+    fn drop(&mut self) {
+        // The bus is released right after, before any woken task gets to run.
+        critical_section::with(|cs| {
+            let mut waiters = self.shared.waiters.borrow_ref_mut(cs);
+            waiters
+                .iter_mut()
+                .flat_map(Option::take)
+                .for_each(Waker::wake);
+        });
+    }
+}
@@ -0,0 +1,61 @@
+//! The frequency of a serial clock divided from the core clock.
+
+use crate::mailbox::{self, Clock};
+
+/// The frequency of a serial clock, along with the divider that achieves it.
+///
+/// The clock is derived from the core clock (the VPU clock): `core / divider`, with an even
+/// divider in the range `2..=MAX_DIVIDER`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Speed<const MAX_DIVIDER: u32> {
+    clock: u32,
+    divider: u32,
+}
+
+impl<const MAX_DIVIDER: u32> Speed<MAX_DIVIDER> {
+    /// The fastest frequency that does not exceed `hz`, computed from the core clock queried from
+    /// the firmware.
+    pub fn from_hz(hz: u32) -> Result<Self, SpeedError> {
+        let clock = mailbox::clock_rate(Clock::Core).ok_or(SpeedError::UnknownClock)?;
+        Self::with_clock(hz, clock)
+    }
+
+    /// The fastest frequency that does not exceed `hz`, with a core clock running at `clock` Hz.
+    pub fn with_clock(hz: u32, clock: u32) -> Result<Self, SpeedError> {
+        let divider = match hz {
+            0 => 0,
+            hz => clock.div_ceil(hz).max(2).next_multiple_of(2),
+        };
+        if !(2..=MAX_DIVIDER).contains(&divider) {
+            return Err(SpeedError::OutOfRange {
+                min: clock.div_ceil(MAX_DIVIDER),
+                max: clock / 2,
+            });
+        }
+
+        Ok(Self { clock, divider })
+    }
+
+    /// The frequency that the divider actually achieves, in Hz.
+    pub fn frequency(&self) -> u32 {
+        self.clock / self.divider
+    }
+
+    /// The core clock the divider was computed from, in Hz.
+    pub fn clock(&self) -> u32 {
+        self.clock
+    }
+
+    pub(crate) fn divider(&self) -> u32 {
+        self.divider
+    }
+}
+
+/// The reasons a frequency cannot be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpeedError {
+    /// The firmware did not report the core clock.
+    UnknownClock,
+    /// The frequency is not reachable with the current core clock.
+    OutOfRange { min: u32, max: u32 },
+}
@@ -0,0 +1,637 @@
+//! The SPI0 master, the full SPI controller of the BCM2835.
+//!
+//! Unlike the aux SPIs, it has 16 entries deep FIFOs, a clock divider down to 2, hardware chip
+//! selects that stay asserted for a whole [`Device`] transaction, a bidirectional 3-wire mode and
+//! LoSSI mode. Transfers are driven by the CPU, from interrupts when async, or by DMA when
+//! [`Config::dma`] is set.
+
+pub mod device;
+mod registers;
+
+pub use crate::speed::SpeedError;
+pub use device::{CsPin, CsPolarity, Device};
+
+use core::{
+    cell::Cell,
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    ptr::{read_volatile, write_volatile},
+    task::{Context, Poll},
+};
+
+use critical_section::Mutex;
+
+use crate::{
+    cache, data_memory_barrier,
+    dma::{self, ControlBlock, Dreq},
+    gpio::{self, state::Alternate0},
+    hal, hal_async, hal_nb,
+    interrupt::{self, Source},
+    set_waker, speed,
+    spi0::registers::*,
+    wake, WakerCell, WAKER_CELL_INIT,
+};
+
+/// The number of entries in each FIFO.
+const FIFO_DEPTH: usize = 16;
+
+const DMA_TX: dma::Channel = dma::Channel::new(4);
+const DMA_RX: dma::Channel = dma::Channel::new(5);
+/// The length of the chunks that DMA transfers are split in.
+const DMA_CHUNK: usize = 4096;
+
+/// The words moved by DMA, and the control blocks that move them.
+///
+/// DMA cannot reach the stack, so the words are copied through these buffers. They are aligned on
+/// cache lines so that maintaining their cache does not touch anything else.
+#[repr(C, align(32))]
+struct DmaBuffers {
+    tx_control: ControlBlock,
+    rx_control: ControlBlock,
+    tx: [u8; DMA_CHUNK],
+    rx: [u8; DMA_CHUNK],
+}
+
+// Only used by the owner of the peripheral.
+static mut DMA_BUFFERS: DmaBuffers = DmaBuffers {
+    tx_control: ControlBlock::EMPTY,
+    rx_control: ControlBlock::EMPTY,
+    tx: [0; DMA_CHUNK],
+    rx: [0; DMA_CHUNK],
+};
+
+static SPI_WAKER: WakerCell = WAKER_CELL_INIT;
+/// Whether the peripheral is owned by a [`Spi0`].
+static TAKEN: Mutex<Cell<bool>> = Mutex::new(Cell::new(false));
+
+/// The SPI0 master, on GPIO 9 (MISO), 10 (MOSI) and 11 (SCLK).
+///
+/// # Implementation notes
+/// - On `read` transactions, we write what is initially present in the buffer.
+/// - Transfers complete before the methods return, so `flush` has nothing to wait for.
+/// - Outside of a [`Device`] transaction, chip select 0 is asserted during each operation.
+/// - In 3-wire mode, `transfer` and `transfer_in_place` write the words, then read.
+/// - With DMA, the transfers that do not fit in the FIFOs are copied through a static buffer, 4096
+///   words at a time.
+pub struct Spi0 {
+    _pins: (
+        gpio::Pin<9, Alternate0>,
+        gpio::Pin<10, Alternate0>,
+        gpio::Pin<11, Alternate0>,
+    ),
+    three_wire: bool,
+    dma: bool,
+    /// A [`Device`] transaction is in progress, so the chip select must stay asserted.
+    in_transaction: bool,
+}
+
+impl Spi0 {
+    pub fn get(
+        miso: gpio::Pin<9, Alternate0>,
+        mosi: gpio::Pin<10, Alternate0>,
+        sclk: gpio::Pin<11, Alternate0>,
+        config: &Config,
+    ) -> Option<Self> {
+        if critical_section::with(|cs| TAKEN.borrow(cs).replace(true)) {
+            return None;
+        }
+
+        let mut control = CS_CLEAR_FIFOS;
+        if config.mode.phase == hal::spi::Phase::CaptureOnSecondTransition {
+            control |= CS_CPHA;
+        }
+        if config.mode.polarity == hal::spi::Polarity::IdleHigh {
+            control |= CS_CPOL;
+        }
+        if config.lossi {
+            control |= CS_LOSSI;
+        }
+        data_memory_barrier();
+        // Safety: Addresses valid, data memory barrier used, and we have exclusive access. Writing
+        // the control register also stops any transfer the firmware may have left active.
+        unsafe {
+            // A divider of 0 stands for 65536.
+            write_volatile(CLOCK_REG, config.speed.divider() & 0xFFFF);
+            write_volatile(CONTROL_STATUS_REG, control);
+        }
+        interrupt::register(Source::SPI, interrupt_handler);
+        let dma = config.dma && !config.lossi;
+        if dma {
+            DMA_TX.reset();
+            DMA_RX.reset();
+            interrupt::register(DMA_RX.interrupt(), dma_interrupt_handler);
+        }
+
+        Some(Self {
+            _pins: (miso, mosi, sclk),
+            three_wire: config.three_wire,
+            dma,
+            in_transaction: false,
+        })
+    }
+
+    /// Assert the chip select, unless a transaction already did, and choose the direction of the
+    /// data line in 3-wire mode.
+    fn start(&mut self, receive: bool) {
+        data_memory_barrier();
+        critical_section::with(|_| {
+            // Safety: Address valid, data memory barrier used, and the interrupt handler only
+            // touches this register in a critical section.
+            let mut control = unsafe { read_volatile(CONTROL_STATUS_REG) };
+            if !self.in_transaction {
+                control = control & !CS_CHIP_SELECT | CS_CLEAR_FIFOS | CS_TRANSFER_ACTIVE;
+            }
+            // A cancelled DMA transfer may have left it set.
+            control &= !CS_DMA_ENABLE;
+            if self.three_wire && receive {
+                control |= CS_READ_ENABLE;
+            } else {
+                control &= !CS_READ_ENABLE;
+            }
+            // Safety: As above.
+            unsafe { write_volatile(CONTROL_STATUS_REG, control) };
+        });
+    }
+
+    /// Deassert the chip select once the transfer is done, unless in a transaction.
+    fn finish(&mut self) {
+        if !self.in_transaction {
+            self.end();
+        }
+    }
+
+    /// Start a transaction on the chip select `index`, which stays asserted until [`Self::end`].
+    fn begin(&mut self, index: u32) {
+        data_memory_barrier();
+        critical_section::with(|_| {
+            // Safety: Address valid, data memory barrier used, and the interrupt handler only
+            // touches this register in a critical section.
+            unsafe {
+                let control = read_volatile(CONTROL_STATUS_REG) & !CS_CHIP_SELECT;
+                write_volatile(
+                    CONTROL_STATUS_REG,
+                    control | index | CS_CLEAR_FIFOS | CS_TRANSFER_ACTIVE,
+                );
+            }
+        });
+        self.in_transaction = true;
+    }
+
+    /// Wait for the last transfer to be done, and deassert the chip select.
+    fn end(&mut self) {
+        data_memory_barrier();
+        // Safety: Address valid, data memory barrier used.
+        while unsafe { read_volatile(CONTROL_STATUS_REG) } & CS_DONE == 0 {
+            core::hint::spin_loop();
+        }
+        critical_section::with(|_| {
+            // Safety: As above, and the interrupt handler only touches this register in a
+            // critical section.
+            unsafe {
+                let control = read_volatile(CONTROL_STATUS_REG);
+                write_volatile(CONTROL_STATUS_REG, control & !CS_TRANSFER_ACTIVE);
+            }
+        });
+        self.in_transaction = false;
+    }
+
+    /// Set the level at which the chip select `index` is asserted.
+    fn set_cs_polarity(&mut self, index: u32, polarity: CsPolarity) {
+        data_memory_barrier();
+        critical_section::with(|_| {
+            // Safety: Address valid, data memory barrier used, and the interrupt handler only
+            // touches this register in a critical section.
+            unsafe {
+                let control = read_volatile(CONTROL_STATUS_REG) & !(CS_POLARITY_0 << index);
+                let polarity = (polarity == CsPolarity::ActiveHigh) as u32 * CS_POLARITY_0;
+                write_volatile(CONTROL_STATUS_REG, control | polarity << index);
+            }
+        });
+    }
+
+    /// Whether a transfer of `len` words goes through DMA.
+    fn uses_dma(&self, len: usize) -> bool {
+        self.dma && len > FIFO_DEPTH
+    }
+
+    /// Run a whole transfer, blocking until it is done.
+    fn transfer_blocking(&mut self, mut words: Words<'_>, receive: bool) {
+        self.start(receive);
+        if self.uses_dma(words.len()) {
+            let mut offset = 0;
+            while offset < words.len() {
+                // Safety: `self` owns the peripheral, and the previous chunk is done.
+                let len = unsafe { start_dma(&words, offset) };
+                while DMA_RX.is_active() {
+                    core::hint::spin_loop();
+                }
+                // Safety: `self` owns the peripheral, and the chunk is done.
+                unsafe { finish_dma(&mut words, offset, len) };
+                offset += len;
+            }
+        } else {
+            let (mut tx_index, mut rx_index) = (0, 0);
+            // Safety: `start` used a data memory barrier.
+            while !unsafe { pump(&mut words, &mut tx_index, &mut rx_index) } {
+                core::hint::spin_loop();
+            }
+            data_memory_barrier();
+        }
+        self.finish();
+    }
+}
+
+impl hal::spi::ErrorType for Spi0 {
+    type Error = Infallible;
+}
+
+impl hal::spi::SpiBus for Spi0 {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.transfer_blocking(Words::InPlace(words), true);
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.transfer_blocking(
+            Words::Split {
+                read: &mut [],
+                write: words,
+            },
+            false,
+        );
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        if self.three_wire {
+            hal::spi::SpiBus::write(self, write)?;
+            return hal::spi::SpiBus::read(self, read);
+        }
+        self.transfer_blocking(Words::Split { read, write }, false);
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        if self.three_wire {
+            hal::spi::SpiBus::write(self, words)?;
+            return hal::spi::SpiBus::read(self, words);
+        }
+        self.transfer_blocking(Words::InPlace(words), false);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// The transfers are cancel-safe, but a cancelled one leaves the chip select asserted until the
+/// next transfer.
+impl hal_async::spi::SpiBus for Spi0 {
+    fn read(&mut self, words: &mut [u8]) -> impl Future<Output = Result<(), Self::Error>> {
+        TransferFut::new(self, Words::InPlace(words), true)
+    }
+
+    fn write(&mut self, words: &[u8]) -> impl Future<Output = Result<(), Self::Error>> {
+        let words = Words::Split {
+            read: &mut [],
+            write: words,
+        };
+        TransferFut::new(self, words, false)
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        if self.three_wire {
+            hal_async::spi::SpiBus::write(self, write).await?;
+            return hal_async::spi::SpiBus::read(self, read).await;
+        }
+        TransferFut::new(self, Words::Split { read, write }, false).await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        if self.three_wire {
+            hal_async::spi::SpiBus::write(self, words).await?;
+            return hal_async::spi::SpiBus::read(self, words).await;
+        }
+        TransferFut::new(self, Words::InPlace(words), false).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+pub struct TransferFut<'a, 'b> {
+    spi: &'a mut Spi0,
+    words: Words<'b>,
+    receive: bool,
+    started: bool,
+    tx_index: usize,
+    rx_index: usize,
+}
+
+impl<'a, 'b> TransferFut<'a, 'b> {
+    fn new(spi: &'a mut Spi0, words: Words<'b>, receive: bool) -> Self {
+        Self {
+            spi,
+            words,
+            receive,
+            started: false,
+            tx_index: 0,
+            rx_index: 0,
+        }
+    }
+}
+
+impl Future for TransferFut<'_, '_> {
+    type Output = Result<(), Infallible>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Self {
+            spi,
+            words,
+            receive,
+            started,
+            tx_index,
+            rx_index,
+        } = &mut *self;
+        if !*started {
+            spi.start(*receive);
+            *started = true;
+        }
+
+        if spi.uses_dma(words.len()) {
+            // With DMA, the chunk being moved spans from `rx_index` to `tx_index`.
+            loop {
+                if *rx_index < *tx_index {
+                    if DMA_RX.is_active() {
+                        break;
+                    }
+                    // Safety: `spi` owns the peripheral, and the chunk is done.
+                    unsafe { finish_dma(words, *rx_index, *tx_index - *rx_index) };
+                    *rx_index = *tx_index;
+                }
+                if *rx_index == words.len() {
+                    spi.finish();
+                    return Poll::Ready(Ok(()));
+                }
+                // Safety: `spi` owns the peripheral, and the previous chunk is done.
+                *tx_index += unsafe { start_dma(words, *rx_index) };
+            }
+
+            critical_section::with(|cs| {
+                set_waker(&SPI_WAKER, cx.waker(), cs);
+                // The chunk may have ended before the waker was set.
+                if !DMA_RX.is_active() {
+                    wake(&SPI_WAKER, cs);
+                }
+            });
+            return Poll::Pending;
+        }
+
+        data_memory_barrier();
+        // Safety: Data memory barrier used.
+        if unsafe { pump(words, tx_index, rx_index) } {
+            data_memory_barrier();
+            spi.finish();
+            return Poll::Ready(Ok(()));
+        }
+
+        critical_section::with(|cs| {
+            set_waker(&SPI_WAKER, cx.waker(), cs);
+            // Safety: Address valid, data memory barrier used, and we have exclusive access.
+            unsafe {
+                let control = read_volatile(CONTROL_STATUS_REG);
+                write_volatile(
+                    CONTROL_STATUS_REG,
+                    control | CS_INTERRUPT_DONE | CS_INTERRUPT_RX,
+                );
+            }
+        });
+        Poll::Pending
+    }
+}
+
+/// One cannot use both this API and the `SpiBus` API at the same time.
+///
+/// The chip select is asserted by the first write, and stays so until the next `SpiBus`
+/// operation.
+impl hal_nb::spi::FullDuplex for Spi0 {
+    fn read(&mut self) -> hal_nb::nb::Result<u8, Self::Error> {
+        data_memory_barrier();
+        // Safety: Address valid, data barrier used.
+        if unsafe { read_volatile(CONTROL_STATUS_REG) } & CS_RX_DATA == 0 {
+            return Err(hal_nb::nb::Error::WouldBlock);
+        }
+        // Safety: As above.
+        Ok(unsafe { read_volatile(FIFO_REG) as u8 })
+    }
+
+    fn write(&mut self, word: u8) -> hal_nb::nb::Result<(), Self::Error> {
+        data_memory_barrier();
+        // Safety: Address valid, data barrier used.
+        let control = unsafe { read_volatile(CONTROL_STATUS_REG) };
+        if control & CS_TRANSFER_ACTIVE == 0 {
+            self.start(false);
+        } else if control & CS_TX_DATA == 0 {
+            return Err(hal_nb::nb::Error::WouldBlock);
+        }
+        // Safety: As above.
+        unsafe { write_volatile(FIFO_REG, word as u32) };
+        Ok(())
+    }
+}
+
+impl Drop for Spi0 {
+    fn drop(&mut self) {
+        data_memory_barrier();
+        // Safety: Address valid, data barrier used.
+        unsafe { write_volatile(CONTROL_STATUS_REG, CS_CLEAR_FIFOS) };
+        interrupt::unregister(Source::SPI);
+        if self.dma {
+            interrupt::unregister(DMA_RX.interrupt());
+            DMA_TX.reset();
+            DMA_RX.reset();
+        }
+        critical_section::with(|cs| TAKEN.borrow(cs).set(false));
+    }
+}
+
+/// The words of a transfer.
+enum Words<'a> {
+    /// The words are sent, and replaced by the ones received.
+    InPlace(&'a mut [u8]),
+    /// The longest of both is transferred, padding `write` with zeros and dropping the extra
+    /// words received.
+    Split { read: &'a mut [u8], write: &'a [u8] },
+}
+
+impl Words<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Words::InPlace(words) => words.len(),
+            Words::Split { read, write } => read.len().max(write.len()),
+        }
+    }
+
+    fn get(&self, index: usize) -> u8 {
+        match self {
+            Words::InPlace(words) => words[index],
+            Words::Split { write, .. } => write.get(index).copied().unwrap_or(0),
+        }
+    }
+
+    fn set(&mut self, index: usize, word: u8) {
+        match self {
+            Words::InPlace(words) => words[index] = word,
+            Words::Split { read, .. } => {
+                if let Some(slot) = read.get_mut(index) {
+                    *slot = word;
+                }
+            }
+        }
+    }
+}
+
+/// Move as many words as possible through the FIFOs, and return whether all of them were received.
+///
+/// The words are only sent when there is room to receive them, so that the transfer never stalls
+/// on a full receive FIFO.
+///
+/// # Safety
+///
+/// A data memory barrier must have been used.
+unsafe fn pump(words: &mut Words<'_>, tx_index: &mut usize, rx_index: &mut usize) -> bool {
+    let len = words.len();
+    loop {
+        // Safety: Address valid, data memory barrier ensured by the caller.
+        let control = unsafe { read_volatile(CONTROL_STATUS_REG) };
+        if *rx_index < *tx_index && control & CS_RX_DATA != 0 {
+            // Safety: As above.
+            let word = unsafe { read_volatile(FIFO_REG) as u8 };
+            words.set(*rx_index, word);
+            *rx_index += 1;
+        } else if *tx_index < len && *tx_index - *rx_index < FIFO_DEPTH && control & CS_TX_DATA != 0
+        {
+            // Safety: As above.
+            unsafe { write_volatile(FIFO_REG, words.get(*tx_index) as u32) };
+            *tx_index += 1;
+        } else {
+            return *rx_index == len;
+        }
+    }
+}
+
+/// Copy the chunk of `words` that starts at `offset` to the DMA buffer, and start moving it.
+///
+/// Returns the length of the chunk.
+///
+/// # Safety
+///
+/// Must be called by the owner of the peripheral, once the previous chunk is done.
+unsafe fn start_dma(words: &Words<'_>, offset: usize) -> usize {
+    let len = (words.len() - offset).min(DMA_CHUNK);
+    // A cancelled transfer may have left the channels running.
+    DMA_TX.reset();
+    DMA_RX.reset();
+
+    let buffers = &raw mut DMA_BUFFERS;
+    // Safety: Only the owner of the peripheral uses the buffers, and the channels were reset.
+    let buffers = unsafe { &mut *buffers };
+    for (index, word) in buffers.tx[..len].iter_mut().enumerate() {
+        *word = words.get(offset + index);
+    }
+    cache::clean_and_invalidate(buffers.tx.as_ptr() as usize, len);
+    // Evict the lines, so that none of them is written back over what DMA receives.
+    cache::clean_and_invalidate(buffers.rx.as_ptr() as usize, len);
+    buffers.tx_control =
+        ControlBlock::to_peripheral(buffers.tx.as_ptr(), len, FIFO_REG, Dreq::SpiTx);
+    buffers.rx_control =
+        ControlBlock::from_peripheral(FIFO_REG, buffers.rx.as_mut_ptr(), len, Dreq::SpiRx);
+
+    data_memory_barrier();
+    critical_section::with(|_| {
+        // Safety: Addresses valid, data memory barrier used, and the interrupt handler only
+        // touches the control register in a critical section. The peripheral stops requesting
+        // words once it moved the length.
+        unsafe {
+            write_volatile(DATA_LENGTH_REG, len as u32);
+            let control = read_volatile(CONTROL_STATUS_REG);
+            write_volatile(CONTROL_STATUS_REG, control | CS_DMA_ENABLE);
+        }
+    });
+    // Safety: The buffers are only touched again once the receiving channel is done, which is
+    // after the transmitting one.
+    unsafe {
+        DMA_RX.start(&buffers.rx_control);
+        DMA_TX.start(&buffers.tx_control);
+    }
+    len
+}
+
+/// Copy the chunk received by DMA to `words`, from `offset`.
+///
+/// # Safety
+///
+/// Must be called by the owner of the peripheral, once the chunk of `len` words is done.
+unsafe fn finish_dma(words: &mut Words<'_>, offset: usize, len: usize) {
+    data_memory_barrier();
+    critical_section::with(|_| {
+        // Safety: Address valid, data memory barrier used, and the interrupt handler only
+        // touches this register in a critical section.
+        unsafe {
+            let control = read_volatile(CONTROL_STATUS_REG);
+            write_volatile(CONTROL_STATUS_REG, control & !CS_DMA_ENABLE);
+        }
+    });
+
+    let buffers = &raw const DMA_BUFFERS;
+    // Safety: Only the owner of the peripheral uses the buffers, and DMA is done with them.
+    let buffers = unsafe { &*buffers };
+    // Safety: The lines were evicted before the chunk started, and the CPU did not write them
+    // since.
+    unsafe { cache::invalidate(buffers.rx.as_ptr() as usize, len) };
+    for (index, &word) in buffers.rx[..len].iter().enumerate() {
+        words.set(offset + index, word);
+    }
+}
+
+// Handle the end of a chunk received by DMA.
+fn dma_interrupt_handler() {
+    DMA_RX.clear_interrupt();
+    critical_section::with(|cs| wake(&SPI_WAKER, cs));
+}
+
+// Handle interrupts that pertain to the SPI.
+pub(crate) fn interrupt_handler() {
+    data_memory_barrier();
+    critical_section::with(|cs| {
+        // Safety: Address is valid, data memory barrier used.
+        unsafe {
+            let control = read_volatile(CONTROL_STATUS_REG);
+            write_volatile(
+                CONTROL_STATUS_REG,
+                control & !(CS_INTERRUPT_DONE | CS_INTERRUPT_RX),
+            );
+        }
+
+        wake(&SPI_WAKER, cs);
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub speed: Speed,
+    pub mode: hal::spi::Mode,
+    /// Use MOSI in both directions, and leave MISO unused.
+    pub three_wire: bool,
+    /// LoSSI mode, see section 10.6.1 of the BCM2835 manual.
+    pub lossi: bool,
+    /// Move the words of the transfers that do not fit in the FIFOs with DMA, on channels 4 and
+    /// 5. Ignored in LoSSI mode.
+    pub dma: bool,
+}
+
+/// The frequency of the SPI clock, along with the divider that achieves it.
+pub type Speed = speed::Speed<65536>;
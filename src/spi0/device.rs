@@ -0,0 +1,117 @@
+//! Devices sharing the SPI0 bus, each with its own hardware chip select.
+
+use core::convert::Infallible;
+
+use embassy_time::Delay;
+
+use crate::{
+    gpio::{self, state::Alternate0},
+    hal::{self, spi::Operation},
+    hal_async,
+    shared_bus::SharedBus,
+    Sealed,
+};
+
+use super::Spi0;
+
+/// The level at which a chip select is asserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum CsPolarity {
+    #[default]
+    ActiveLow,
+    ActiveHigh,
+}
+
+/// A device on the SPI0 bus, selected by `cs`.
+///
+/// The chip select stays asserted for the whole transaction, including the delays. The bus is
+/// borrowed for the duration of each transaction.
+pub struct Device<'a, CS> {
+    bus: &'a SharedBus<Spi0>,
+    cs: CS,
+}
+
+impl<'a, CS: CsPin> Device<'a, CS> {
+    /// Create a device on `bus`, whose chip select is asserted at the `polarity` level.
+    ///
+    /// Panics if the bus is borrowed.
+    pub fn new(bus: &'a SharedBus<Spi0>, cs: CS, polarity: CsPolarity) -> Self {
+        bus.borrow().set_cs_polarity(CS::INDEX, polarity);
+        Self { bus, cs }
+    }
+
+    /// Give back the chip select pin.
+    pub fn release(self) -> CS {
+        self.cs
+    }
+}
+
+impl<CS: CsPin> hal::spi::ErrorType for Device<'_, CS> {
+    type Error = Infallible;
+}
+
+impl<CS: CsPin> hal::spi::SpiDevice for Device<'_, CS> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        use hal::{delay::DelayNs, spi::SpiBus};
+
+        let mut bus = self.bus.borrow();
+        bus.begin(CS::INDEX);
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => bus.read(words)?,
+                Operation::Write(words) => bus.write(words)?,
+                Operation::Transfer(read, write) => bus.transfer(read, write)?,
+                Operation::TransferInPlace(words) => bus.transfer_in_place(words)?,
+                Operation::DelayNs(ns) => Delay.delay_ns(*ns),
+            }
+        }
+        bus.end();
+        Ok(())
+    }
+}
+
+/// Dropping the future in the middle of a transaction leaves the chip select asserted until the
+/// next one.
+impl<CS: CsPin> hal_async::spi::SpiDevice for Device<'_, CS> {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        use hal_async::{delay::DelayNs, spi::SpiBus};
+
+        let mut bus = self.bus.wait_borrow().await;
+        bus.begin(CS::INDEX);
+        for operation in operations {
+            match operation {
+                Operation::Read(words) => bus.read(words).await?,
+                Operation::Write(words) => bus.write(words).await?,
+                Operation::Transfer(read, write) => bus.transfer(read, write).await?,
+                Operation::TransferInPlace(words) => bus.transfer_in_place(words).await?,
+                Operation::DelayNs(ns) => Delay.delay_ns(*ns).await,
+            }
+        }
+        bus.end();
+        Ok(())
+    }
+}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as a chip select of SPI0.
+#[allow(private_bounds)]
+pub trait CsPin: Sealed {
+    /// The number of the chip select.
+    const INDEX: u32;
+}
+
+// See the BCM2835 manual section 6.2 for the pin mappings.
+impl CsPin for gpio::Pin<8, Alternate0> {
+    const INDEX: u32 = 0;
+}
+impl CsPin for gpio::Pin<7, Alternate0> {
+    const INDEX: u32 = 1;
+}
+impl CsPin for gpio::Pin<36, Alternate0> {
+    const INDEX: u32 = 0;
+}
+impl CsPin for gpio::Pin<35, Alternate0> {
+    const INDEX: u32 = 1;
+}
@@ -0,0 +1,28 @@
+/// SPI Master Control and Status
+/// BCM2835 ARM Peripherals, page 153
+pub const CONTROL_STATUS_REG: *mut u32 = 0x20204000 as _;
+/// SPI Master TX and RX FIFOs
+/// BCM2835 ARM Peripherals, page 155
+pub const FIFO_REG: *mut u32 = 0x20204004 as _;
+/// SPI Master Clock Divider
+/// BCM2835 ARM Peripherals, page 156
+pub const CLOCK_REG: *mut u32 = 0x20204008 as _;
+/// SPI Master Data Length
+/// BCM2835 ARM Peripherals, page 156
+pub const DATA_LENGTH_REG: *mut u32 = 0x2020400C as _;
+
+pub const CS_CHIP_SELECT: u32 = 0b11;
+pub const CS_CPHA: u32 = 1 << 2;
+pub const CS_CPOL: u32 = 1 << 3;
+pub const CS_CLEAR_FIFOS: u32 = 0b11 << 4;
+pub const CS_TRANSFER_ACTIVE: u32 = 1 << 7;
+pub const CS_DMA_ENABLE: u32 = 1 << 8;
+pub const CS_INTERRUPT_DONE: u32 = 1 << 9;
+pub const CS_INTERRUPT_RX: u32 = 1 << 10;
+pub const CS_READ_ENABLE: u32 = 1 << 12;
+pub const CS_LOSSI: u32 = 1 << 13;
+pub const CS_DONE: u32 = 1 << 16;
+pub const CS_RX_DATA: u32 = 1 << 17;
+pub const CS_TX_DATA: u32 = 1 << 18;
+/// The polarity of chip select 0, the next ones follow.
+pub const CS_POLARITY_0: u32 = 1 << 21;
@@ -0,0 +1,286 @@
+//! The PL011 UART, also referred to as `UART0`.
+//!
+//! Unlike the Mini UART, its clock does not depend on the VPU clock, it has 16 entries deep FIFOs,
+//! and supports parity, hardware flow control and break.
+
+pub mod reader;
+mod registers;
+pub mod writer;
+
+pub use reader::Reader;
+pub use writer::Writer;
+
+use core::{
+    cell::Cell,
+    ptr::{read_volatile, write_volatile},
+};
+
+use critical_section::{CriticalSection, Mutex};
+
+use crate::{
+    data_memory_barrier,
+    interrupt::{self, Source},
+    serial::{FifoLevel, Parity, StopBits, WordLength},
+    uart0::registers::*,
+};
+
+use reader::{RtsPin, RxPin};
+use writer::{CtsPin, TxPin};
+
+// This is the clock of the UART, set by `init_uart_clock` in `config.txt`. The firmware defaults
+// to 48 MHz.
+pub const CLOCK_SPEED: u32 = 48_000_000;
+
+// The configuration applied when the first half was enabled, which the other half must match.
+static ACTIVE_CONFIG: Mutex<Cell<Option<Config>>> = Mutex::new(Cell::new(None));
+
+pub fn pair<RP: RxPin, TP: TxPin>(
+    rx_pin: RP,
+    tx_pin: TP,
+    config: &Config,
+) -> Option<(reader::Reader<RP>, writer::Writer<TP>)> {
+    // Safety: Both halves are acquired at once.
+    unsafe { acquire(CONTROL_RX_ENABLE | CONTROL_TX_ENABLE, config) }?;
+
+    Some((
+        reader::Reader {
+            _rx_pin: rx_pin,
+            _rts_pin: (),
+        },
+        writer::Writer {
+            _tx_pin: tx_pin,
+            _cts_pin: (),
+        },
+    ))
+}
+
+/// Like [`pair`], with hardware flow control.
+///
+/// Data is only sent while CTS is asserted, and RTS is deasserted when the receive FIFO is full.
+pub fn pair_with_flow_control<RP: RxPin, TP: TxPin, R: RtsPin, C: CtsPin>(
+    rx_pin: RP,
+    tx_pin: TP,
+    rts_pin: R,
+    cts_pin: C,
+    config: &Config,
+) -> Option<(reader::Reader<RP, R>, writer::Writer<TP, C>)> {
+    let halves = CONTROL_RX_ENABLE | CONTROL_RTS_ENABLE | CONTROL_TX_ENABLE | CONTROL_CTS_ENABLE;
+    // Safety: Both halves are acquired at once.
+    unsafe { acquire(halves, config) }?;
+
+    Some((
+        reader::Reader {
+            _rx_pin: rx_pin,
+            _rts_pin: rts_pin,
+        },
+        writer::Writer {
+            _tx_pin: tx_pin,
+            _cts_pin: cts_pin,
+        },
+    ))
+}
+
+/// Enable the halves of the UART in `halves`, and apply `config`.
+///
+/// The configuration is shared by both halves, so it is only applied if the other half is not in
+/// use. Returns `None` if one of the halves is already in use, or if the other half is in use with
+/// a different configuration.
+///
+/// # Safety
+///
+/// `halves` must be a combination of `CONTROL_RX_ENABLE` and `CONTROL_TX_ENABLE`, along with the
+/// flow control bit of each of them, `CONTROL_RTS_ENABLE` and `CONTROL_CTS_ENABLE`.
+unsafe fn acquire(halves: u32, config: &Config) -> Option<()> {
+    data_memory_barrier();
+
+    critical_section::with(|cs| {
+        // Safety: Address is valid, and a memory barrier is used. A half is not acquired if it is
+        // already enabled. Critical section used so that two threads do not race to acquire it.
+        let control_reg = unsafe { read_volatile(CONTROL_REG) };
+        if control_reg & halves != 0 {
+            return None;
+        }
+        if control_reg & (CONTROL_RX_ENABLE | CONTROL_TX_ENABLE) == 0 {
+            config.setup(control_reg | halves, cs);
+        } else if ACTIVE_CONFIG.borrow(cs).get() == Some(*config) {
+            // Safety: Address is valid, and a memory barrier is used. The UART is already
+            // configured, enabling a half does not flush the FIFOs of the other one.
+            unsafe { write_volatile(CONTROL_REG, control_reg | halves) };
+        } else {
+            return None;
+        }
+        interrupt::register(Source::UART, interrupt_handler);
+        Some(())
+    })
+}
+
+/// Disable the halves of the UART in `halves`, and unbind the interrupt handler once both are.
+fn release(halves: u32) {
+    data_memory_barrier();
+
+    critical_section::with(|_| {
+        // Safety: Address is valid, and a memory barrier is used. A critical section is used so
+        // that two threads do not race to acquire the lock.
+        let control_reg = unsafe {
+            let control_reg = read_volatile(CONTROL_REG) & !halves;
+            write_volatile(CONTROL_REG, control_reg);
+            control_reg
+        };
+        if control_reg & (CONTROL_RX_ENABLE | CONTROL_TX_ENABLE) == 0 {
+            interrupt::unregister(Source::UART);
+        }
+    })
+}
+
+/// Disable the UART and its interrupts.
+///
+/// This is done by the runtime before `main` when the `rt` feature is enabled, code that brings
+/// its own entry point must call it before using the UART.
+///
+/// # Safety
+///
+/// Must be called once, before the UART is used.
+pub unsafe fn setup(_cs: &CriticalSection) {
+    data_memory_barrier();
+    // Safety: Addresses valid, data memory barrier used. The firmware may have left the UART
+    // enabled for its console.
+    unsafe {
+        write_volatile(CONTROL_REG, 0);
+        write_volatile(INTERRUPT_MASK_REG, 0);
+        write_volatile(INTERRUPT_CLEAR_REG, INTERRUPT_ALL);
+    }
+}
+
+// Handle interrupts that pertain to the UART.
+pub(crate) fn interrupt_handler() {
+    data_memory_barrier();
+    // Safety: Address is valid, data memory barrier used.
+    let interrupts = unsafe { read_volatile(MASKED_INTERRUPT_REG) };
+    if interrupts & INTERRUPT_TX != 0 {
+        writer::interrupt_handler();
+    }
+    if interrupts & (INTERRUPT_RX | INTERRUPT_RX_TIMEOUT) != 0 {
+        reader::interrupt_handler();
+    }
+}
+
+/// Mask or unmask `interrupts`, and clear them.
+fn set_interrupts(interrupts: u32, enabled: bool) {
+    data_memory_barrier();
+    critical_section::with(|_| {
+        // Safety: Addresses are valid, data memory barrier used.
+        unsafe {
+            let mut reg = INTERRUPT_MASK_REG.read_volatile();
+            if enabled {
+                reg |= interrupts;
+            } else {
+                reg &= !interrupts;
+            }
+            INTERRUPT_MASK_REG.write_volatile(reg);
+            INTERRUPT_CLEAR_REG.write_volatile(interrupts);
+        }
+    });
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BaudRate(u32);
+
+impl BaudRate {
+    const MIN: u32 = 46;
+    const MAX: u32 = CLOCK_SPEED / 16;
+
+    /// Returns an error if the baud rate is not reachable with `CLOCK_SPEED`.
+    pub fn new(baud_rate: u32) -> Result<Self, BaudRateError> {
+        if !(Self::MIN..=Self::MAX).contains(&baud_rate) {
+            return Err(BaudRateError::OutOfRange {
+                min: Self::MIN,
+                max: Self::MAX,
+            });
+        }
+        Ok(Self(baud_rate))
+    }
+
+    /// The integer and fractional divisors.
+    ///
+    /// The divisor is `CLOCK_SPEED / (16 * baud_rate)`, with a fractional part in 64ths.
+    fn register_values(&self) -> (u32, u32) {
+        let divisor = (4 * CLOCK_SPEED + self.0 / 2) / self.0;
+        (divisor >> 6, divisor & 0x3F)
+    }
+}
+
+/// The reasons a baud rate cannot be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BaudRateError {
+    /// The baud rate is not reachable with `CLOCK_SPEED`.
+    OutOfRange { min: u32, max: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Config {
+    pub baud_rate: BaudRate,
+    pub word_length: WordLength,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub rx_fifo_level: FifoLevel,
+    pub tx_fifo_level: FifoLevel,
+}
+
+impl Default for Config {
+    /// 115200 baud, 8 bits, no parity, one stop bit.
+    fn default() -> Self {
+        Self {
+            baud_rate: BaudRate(115_200),
+            word_length: WordLength::default(),
+            parity: Parity::default(),
+            stop_bits: StopBits::default(),
+            rx_fifo_level: FifoLevel::default(),
+            tx_fifo_level: FifoLevel::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Apply the configuration, and write `control` to the control register.
+    ///
+    /// The configuration is shared by the reader and the writer, so this flushes the FIFOs of
+    /// both.
+    fn setup(&self, control: u32, cs: CriticalSection) {
+        let (integer, fractional) = self.baud_rate.register_values();
+        let mut line_control = LINE_FIFO_ENABLE | (self.word_length as u32) << 5;
+        line_control |= match self.parity {
+            Parity::None => 0,
+            Parity::Even => LINE_PARITY_ENABLE | LINE_EVEN_PARITY,
+            Parity::Odd => LINE_PARITY_ENABLE,
+            Parity::One => LINE_PARITY_ENABLE | LINE_STICK_PARITY,
+            Parity::Zero => LINE_PARITY_ENABLE | LINE_STICK_PARITY | LINE_EVEN_PARITY,
+        };
+        if self.stop_bits == StopBits::Two {
+            line_control |= LINE_TWO_STOP_BITS;
+        }
+
+        data_memory_barrier();
+        // Safety: Valid addresses used, data memory barrier used. The UART must be done
+        // transmitting and disabled while it is configured, and disabling the FIFOs flushes them.
+        // The divisors are only latched when the line control register is written.
+        unsafe {
+            let transmitting = CONTROL_ENABLE | CONTROL_TX_ENABLE;
+            if read_volatile(CONTROL_REG) & transmitting == transmitting {
+                while read_volatile(FLAG_REG) & FLAG_BUSY != 0 {
+                    core::hint::spin_loop();
+                }
+            }
+            write_volatile(CONTROL_REG, 0);
+            write_volatile(LINE_CONTROL_REG, 0);
+            write_volatile(INTEGER_BAUD_RATE_REG, integer);
+            write_volatile(FRACTIONAL_BAUD_RATE_REG, fractional);
+            write_volatile(LINE_CONTROL_REG, line_control);
+            write_volatile(
+                FIFO_LEVEL_REG,
+                (self.rx_fifo_level as u32) << 3 | self.tx_fifo_level as u32,
+            );
+            write_volatile(CONTROL_REG, control | CONTROL_ENABLE);
+        }
+        ACTIVE_CONFIG.borrow(cs).set(Some(*self));
+    }
+}
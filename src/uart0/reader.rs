@@ -0,0 +1,357 @@
+use core::{
+    cell::Cell,
+    future::Future,
+    ptr::{read_volatile, write_volatile},
+    slice::IterMut,
+    task::Poll,
+};
+
+use critical_section::Mutex;
+use embedded_io::ReadExactError;
+
+use crate::{
+    data_memory_barrier, eio, eio_async,
+    gpio::{
+        self,
+        state::{Alternate0, Alternate2, Alternate3},
+    },
+    hal_nb,
+    interrupt::{self, Source},
+    set_waker,
+    uart0::{registers::*, Config},
+    wake, Sealed, WakerCell, WAKER_CELL_INIT,
+};
+
+static READER_WAKER: WakerCell = WAKER_CELL_INIT;
+/// An error to return on the next read, after the bytes received before it.
+static PENDING_ERROR: Mutex<Cell<Option<Error>>> = Mutex::new(Cell::new(None));
+
+/// The receiver, along with its RTS pin when hardware flow control is used.
+#[derive(Debug)]
+pub struct Reader<P, R = ()> {
+    pub(super) _rx_pin: P,
+    pub(super) _rts_pin: R,
+}
+
+impl<P: RxPin> Reader<P> {
+    /// Enable the receiver and apply `config`, which is shared with the writer.
+    ///
+    /// Returns `None` if the receiver is already in use.
+    pub fn get(rx_pin: P, config: &Config) -> Option<Self> {
+        // Safety: Only the receiver is acquired.
+        unsafe { super::acquire(CONTROL_RX_ENABLE, config) }?;
+        Some(Self {
+            _rx_pin: rx_pin,
+            _rts_pin: (),
+        })
+    }
+
+    /// Get the reader without checking if it is already in use.
+    ///
+    /// # Safety
+    ///
+    /// UB if the reader is already in use.
+    pub unsafe fn get_unchecked(rx_pin: P, config: &Config) -> Self {
+        data_memory_barrier();
+        critical_section::with(|cs| {
+            // Safety: Address is valid, and a memory barrier is used.
+            let control_reg = unsafe { read_volatile(CONTROL_REG) };
+            config.setup(control_reg | CONTROL_RX_ENABLE, cs);
+            interrupt::register(Source::UART, super::interrupt_handler);
+        });
+        Self {
+            _rx_pin: rx_pin,
+            _rts_pin: (),
+        }
+    }
+}
+
+impl<P: RxPin, R: RtsPin> Reader<P, R> {
+    /// Enable the receiver with hardware flow control, and apply `config`, which is shared with
+    /// the writer.
+    ///
+    /// RTS is deasserted when the receive FIFO is full. Returns `None` if the receiver is already
+    /// in use.
+    pub fn with_rts(rx_pin: P, rts_pin: R, config: &Config) -> Option<Self> {
+        // Safety: Only the receiver is acquired.
+        unsafe { super::acquire(CONTROL_RX_ENABLE | CONTROL_RTS_ENABLE, config) }?;
+        Some(Self {
+            _rx_pin: rx_pin,
+            _rts_pin: rts_pin,
+        })
+    }
+}
+
+impl<P, R> Drop for Reader<P, R> {
+    fn drop(&mut self) {
+        critical_section::with(|cs| PENDING_ERROR.borrow(cs).set(None));
+        super::release(CONTROL_RX_ENABLE | CONTROL_RTS_ENABLE);
+    }
+}
+
+/// Return `error` on the next read.
+fn defer(error: Error) {
+    critical_section::with(|cs| PENDING_ERROR.borrow(cs).set(Some(error)));
+}
+
+/// Read a byte from the receive FIFO, if it is not empty.
+///
+/// A byte flagged with an overrun is valid, the lost one is the next, so the byte is returned and
+/// the overrun is returned by the next read.
+fn receive() -> Option<Result<u8, Error>> {
+    if let Some(error) = critical_section::with(|cs| PENDING_ERROR.borrow(cs).take()) {
+        return Some(Err(error));
+    }
+    data_memory_barrier();
+    // Safety: Address is valid, data memory barrier used.
+    if unsafe { read_volatile(FLAG_REG) } & FLAG_RX_EMPTY != 0 {
+        return None;
+    }
+    // Safety: As above.
+    let data = unsafe { read_volatile(DATA_REG) };
+    data_memory_barrier();
+    if data & DATA_ERRORS != 0 {
+        // Safety: Address is valid. Writing any value clears the errors.
+        unsafe { write_volatile(RECEIVE_STATUS_REG, 0) };
+    }
+    match (Error::from_data(data), data & DATA_OVERRUN != 0) {
+        (None, true) => {
+            defer(Error::Overrun);
+            Some(Ok(data as u8))
+        }
+        (None, false) => Some(Ok(data as u8)),
+        // The byte is dropped anyway, so the overrun is returned in its place.
+        (Some(_), true) => Some(Err(Error::Overrun)),
+        (Some(error), false) => Some(Err(error)),
+    }
+}
+
+impl<P: RxPin, R> eio::ErrorType for Reader<P, R> {
+    type Error = Error;
+}
+
+impl<P: RxPin, R> hal_nb::serial::ErrorType for Reader<P, R> {
+    type Error = Error;
+}
+
+impl<P: RxPin, R> eio::Read for Reader<P, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        for (count, byte) in buf.iter_mut().enumerate() {
+            loop {
+                match receive() {
+                    Some(Ok(received)) => {
+                        *byte = received;
+                        break;
+                    }
+                    Some(Err(error)) if count != 0 => {
+                        defer(error);
+                        return Ok(count);
+                    }
+                    Some(Err(error)) => return Err(error),
+                    None if count != 0 => return Ok(count),
+                    None => {}
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn read_exact(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(), embedded_io::ReadExactError<Self::Error>> {
+        for byte in buf {
+            *byte = loop {
+                if let Some(result) = receive() {
+                    break result?;
+                }
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<P: RxPin, R> eio::ReadReady for Reader<P, R> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        // A pending error is returned by the next read without waiting.
+        if critical_section::with(|cs| PENDING_ERROR.borrow(cs).get().is_some()) {
+            return Ok(true);
+        }
+        data_memory_barrier();
+        // Safety: Address is valid, data memory barrier used.
+        Ok(unsafe { FLAG_REG.read_volatile() } & FLAG_RX_EMPTY == 0)
+    }
+}
+
+impl<P: RxPin, R> hal_nb::serial::Read for Reader<P, R> {
+    fn read(&mut self) -> hal_nb::nb::Result<u8, Self::Error> {
+        match receive() {
+            Some(result) => result.map_err(hal_nb::nb::Error::Other),
+            None => Err(hal_nb::nb::Error::WouldBlock),
+        }
+    }
+}
+
+/// This implementation is cancel-safe.
+///
+/// The receive interrupt triggers once the FIFO reaches `Config::rx_fifo_level`, or when data has
+/// been waiting in it for 32 bits, so `read` returns as many bytes as were received together.
+impl<P: RxPin, R> eio_async::Read for Reader<P, R> {
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = Result<usize, Self::Error>> {
+        ReadFut { _reader: self, buf }
+    }
+
+    fn read_exact(
+        &mut self,
+        buf: &mut [u8],
+    ) -> impl Future<Output = Result<(), ReadExactError<Self::Error>>> {
+        ReadExactFut {
+            _reader: self,
+            buf_iter: buf.iter_mut(),
+        }
+    }
+}
+
+/// Wake the current task when data is received.
+///
+/// The interrupts are level triggered, so data that arrived before they are enabled still wakes
+/// the task.
+fn wait(cx: &core::task::Context) {
+    critical_section::with(|cs| set_waker(&READER_WAKER, cx.waker(), cs));
+    super::set_interrupts(INTERRUPT_RX | INTERRUPT_RX_TIMEOUT, true);
+}
+
+#[derive(Debug)]
+pub struct ReadFut<'a, 'b, P, R = ()> {
+    _reader: &'a mut Reader<P, R>,
+    buf: &'b mut [u8],
+}
+
+impl<P: RxPin, R> Future for ReadFut<'_, '_, P, R> {
+    type Output = Result<usize, Error>;
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context,
+    ) -> Poll<Self::Output> {
+        for (count, byte) in self.buf.iter_mut().enumerate() {
+            match receive() {
+                Some(Ok(received)) => *byte = received,
+                Some(Err(error)) if count != 0 => {
+                    defer(error);
+                    return Poll::Ready(Ok(count));
+                }
+                Some(Err(error)) => return Poll::Ready(Err(error)),
+                None if count != 0 => return Poll::Ready(Ok(count)),
+                None => {
+                    wait(cx);
+                    return Poll::Pending;
+                }
+            }
+        }
+        Poll::Ready(Ok(self.buf.len()))
+    }
+}
+
+#[derive(Debug)]
+pub struct ReadExactFut<'a, 'b, P, R = ()> {
+    _reader: &'a mut Reader<P, R>,
+    buf_iter: IterMut<'b, u8>,
+}
+
+impl<P: RxPin, R> Future for ReadExactFut<'_, '_, P, R> {
+    type Output = Result<(), ReadExactError<Error>>;
+
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> Poll<Self::Output> {
+        while self.buf_iter.len() != 0 {
+            match receive() {
+                Some(Ok(received)) => {
+                    if let Some(byte) = self.buf_iter.next() {
+                        *byte = received;
+                    }
+                }
+                Some(Err(error)) => return Poll::Ready(Err(ReadExactError::Other(error))),
+                None => {
+                    wait(cx);
+                    return Poll::Pending;
+                }
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub(super) fn interrupt_handler() {
+    super::set_interrupts(INTERRUPT_RX | INTERRUPT_RX_TIMEOUT, false);
+    critical_section::with(|cs| wake(&READER_WAKER, cs));
+}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the UART RX pin.
+#[allow(private_bounds)]
+pub trait RxPin: Sealed {}
+
+// See the BCM2835 manual section 6.2 for the pin mappings.
+impl RxPin for gpio::Pin<15, Alternate0> {}
+impl RxPin for gpio::Pin<33, Alternate3> {}
+impl RxPin for gpio::Pin<37, Alternate2> {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the UART RTS pin.
+#[allow(private_bounds)]
+pub trait RtsPin: Sealed {}
+
+impl RtsPin for gpio::Pin<17, Alternate3> {}
+impl RtsPin for gpio::Pin<31, Alternate3> {}
+impl RtsPin for gpio::Pin<38, Alternate2> {}
+
+/// Errors that can occurs when reading from the UART.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Error {
+    /// The receive FIFO was full, and the byte after the last one read was lost.
+    Overrun,
+    /// The line was held low for longer than a full word.
+    Break,
+    /// The parity of the received byte does not match.
+    Parity,
+    /// The received byte did not end with a valid stop bit.
+    Framing,
+}
+
+impl Error {
+    /// The error of the byte in a word read from the data register, if any.
+    ///
+    /// An overrun does not concern the byte it flags, so it is not one of them.
+    fn from_data(data: u32) -> Option<Self> {
+        if data & DATA_BREAK != 0 {
+            Some(Error::Break)
+        } else if data & DATA_PARITY != 0 {
+            Some(Error::Parity)
+        } else if data & DATA_FRAMING != 0 {
+            Some(Error::Framing)
+        } else {
+            None
+        }
+    }
+}
+
+impl eio::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Error::Parity | Error::Framing => eio::ErrorKind::InvalidData,
+            Error::Overrun | Error::Break => eio::ErrorKind::Other,
+        }
+    }
+}
+
+impl hal_nb::serial::Error for Error {
+    fn kind(&self) -> hal_nb::serial::ErrorKind {
+        match self {
+            Error::Overrun => hal_nb::serial::ErrorKind::Overrun,
+            Error::Break => hal_nb::serial::ErrorKind::Other,
+            Error::Parity => hal_nb::serial::ErrorKind::Parity,
+            Error::Framing => hal_nb::serial::ErrorKind::FrameFormat,
+        }
+    }
+}
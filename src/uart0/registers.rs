@@ -0,0 +1,66 @@
+/// UART Data
+/// BCM2835 ARM Peripherals, section 13.4
+pub const DATA_REG: *mut u32 = 0x20201000 as _;
+/// UART Receive Status / Error Clear
+/// BCM2835 ARM Peripherals, section 13.4
+pub const RECEIVE_STATUS_REG: *mut u32 = 0x20201004 as _;
+/// UART Flag
+/// BCM2835 ARM Peripherals, section 13.4
+pub const FLAG_REG: *mut u32 = 0x20201018 as _;
+/// UART Integer Baud Rate Divisor
+/// BCM2835 ARM Peripherals, section 13.4
+pub const INTEGER_BAUD_RATE_REG: *mut u32 = 0x20201024 as _;
+/// UART Fractional Baud Rate Divisor
+/// BCM2835 ARM Peripherals, section 13.4
+pub const FRACTIONAL_BAUD_RATE_REG: *mut u32 = 0x20201028 as _;
+/// UART Line Control
+/// BCM2835 ARM Peripherals, section 13.4
+pub const LINE_CONTROL_REG: *mut u32 = 0x2020102C as _;
+/// UART Control
+/// BCM2835 ARM Peripherals, section 13.4
+pub const CONTROL_REG: *mut u32 = 0x20201030 as _;
+/// UART Interrupt FIFO Level Select
+/// BCM2835 ARM Peripherals, section 13.4
+pub const FIFO_LEVEL_REG: *mut u32 = 0x20201034 as _;
+/// UART Interrupt Mask Set/Clear
+/// BCM2835 ARM Peripherals, section 13.4
+pub const INTERRUPT_MASK_REG: *mut u32 = 0x20201038 as _;
+/// UART Masked Interrupt Status
+/// BCM2835 ARM Peripherals, section 13.4
+pub const MASKED_INTERRUPT_REG: *mut u32 = 0x20201040 as _;
+/// UART Interrupt Clear
+/// BCM2835 ARM Peripherals, section 13.4
+pub const INTERRUPT_CLEAR_REG: *mut u32 = 0x20201044 as _;
+
+// Data register bits.
+pub const DATA_FRAMING: u32 = 1 << 8;
+pub const DATA_PARITY: u32 = 1 << 9;
+pub const DATA_BREAK: u32 = 1 << 10;
+pub const DATA_OVERRUN: u32 = 1 << 11;
+pub const DATA_ERRORS: u32 = 0xF00;
+
+// Flag register bits.
+pub const FLAG_BUSY: u32 = 1 << 3;
+pub const FLAG_RX_EMPTY: u32 = 1 << 4;
+pub const FLAG_TX_FULL: u32 = 1 << 5;
+
+// Line control register bits.
+pub const LINE_BREAK: u32 = 1;
+pub const LINE_PARITY_ENABLE: u32 = 1 << 1;
+pub const LINE_EVEN_PARITY: u32 = 1 << 2;
+pub const LINE_TWO_STOP_BITS: u32 = 1 << 3;
+pub const LINE_FIFO_ENABLE: u32 = 1 << 4;
+pub const LINE_STICK_PARITY: u32 = 1 << 7;
+
+// Control register bits.
+pub const CONTROL_ENABLE: u32 = 1;
+pub const CONTROL_TX_ENABLE: u32 = 1 << 8;
+pub const CONTROL_RX_ENABLE: u32 = 1 << 9;
+pub const CONTROL_RTS_ENABLE: u32 = 1 << 14;
+pub const CONTROL_CTS_ENABLE: u32 = 1 << 15;
+
+// Interrupt bits, shared by the mask, status and clear registers.
+pub const INTERRUPT_RX: u32 = 1 << 4;
+pub const INTERRUPT_TX: u32 = 1 << 5;
+pub const INTERRUPT_RX_TIMEOUT: u32 = 1 << 6;
+pub const INTERRUPT_ALL: u32 = 0x7FF;
@@ -0,0 +1,278 @@
+use core::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    ptr::{read_volatile, write_volatile},
+    task::{Context, Poll},
+};
+
+use crate::{
+    data_memory_barrier, eio, eio_async,
+    gpio::{
+        self,
+        state::{Alternate0, Alternate2, Alternate3},
+    },
+    hal_nb,
+    interrupt::{self, Source},
+    set_waker,
+    uart0::{registers::*, Config},
+    wake, Sealed, WakerCell, WAKER_CELL_INIT,
+};
+
+static WRITER_WAKER: WakerCell = WAKER_CELL_INIT;
+
+/// The transmitter, along with its CTS pin when hardware flow control is used.
+#[derive(Debug)]
+pub struct Writer<P, C = ()> {
+    pub(super) _tx_pin: P,
+    pub(super) _cts_pin: C,
+}
+
+impl<P: TxPin> Writer<P> {
+    /// Enable the transmitter and apply `config`, which is shared with the reader.
+    ///
+    /// Returns `None` if the transmitter is already in use.
+    pub fn get(tx_pin: P, config: &Config) -> Option<Self> {
+        // Safety: Only the transmitter is acquired.
+        unsafe { super::acquire(CONTROL_TX_ENABLE, config) }?;
+        Some(Self {
+            _tx_pin: tx_pin,
+            _cts_pin: (),
+        })
+    }
+
+    /// Get the writer without checking if it is already in use.
+    ///
+    /// # Safety
+    ///
+    /// UB if the writer is already in use.
+    pub unsafe fn get_unchecked(tx_pin: P, config: &Config) -> Self {
+        data_memory_barrier();
+        critical_section::with(|cs| {
+            // Safety: Address is valid, and a memory barrier is used.
+            let control_reg = unsafe { read_volatile(CONTROL_REG) };
+            config.setup(control_reg | CONTROL_TX_ENABLE, cs);
+            interrupt::register(Source::UART, super::interrupt_handler);
+        });
+        Self {
+            _tx_pin: tx_pin,
+            _cts_pin: (),
+        }
+    }
+}
+
+impl<P: TxPin, C: CtsPin> Writer<P, C> {
+    /// Enable the transmitter with hardware flow control, and apply `config`, which is shared
+    /// with the reader.
+    ///
+    /// Data is only sent while CTS is asserted. Returns `None` if the transmitter is already in
+    /// use.
+    pub fn with_cts(tx_pin: P, cts_pin: C, config: &Config) -> Option<Self> {
+        // Safety: Only the transmitter is acquired.
+        unsafe { super::acquire(CONTROL_TX_ENABLE | CONTROL_CTS_ENABLE, config) }?;
+        Some(Self {
+            _tx_pin: tx_pin,
+            _cts_pin: cts_pin,
+        })
+    }
+}
+
+impl<P: TxPin, C> Writer<P, C> {
+    /// Hold the TX line low while `enabled`, which the other end detects as a break.
+    ///
+    /// The break starts after the byte being sent, and must be held for at least two frames.
+    pub fn set_break(&mut self, enabled: bool) {
+        data_memory_barrier();
+        critical_section::with(|_| {
+            // Safety: Address is valid, and a memory barrier is used. Writing the line control
+            // register again latches the same baud rate divisors.
+            unsafe {
+                let mut reg = read_volatile(LINE_CONTROL_REG);
+                if enabled {
+                    reg |= LINE_BREAK;
+                } else {
+                    reg &= !LINE_BREAK;
+                }
+                write_volatile(LINE_CONTROL_REG, reg);
+            }
+        });
+    }
+}
+
+impl<P, C> Drop for Writer<P, C> {
+    fn drop(&mut self) {
+        super::release(CONTROL_TX_ENABLE | CONTROL_CTS_ENABLE);
+    }
+}
+
+/// Write bytes of `buf` to the transmit FIFO until it is full.
+///
+/// Returns the number of bytes written.
+fn transmit(buf: &[u8]) -> usize {
+    data_memory_barrier();
+    let mut written = 0;
+    for byte in buf {
+        // Safety: Address is valid, memory barrier used.
+        if unsafe { FLAG_REG.read_volatile() } & FLAG_TX_FULL != 0 {
+            break;
+        }
+        // Safety: As above.
+        unsafe { DATA_REG.write_volatile(*byte as u32) };
+        written += 1;
+    }
+    data_memory_barrier();
+    written
+}
+
+fn busy() -> bool {
+    data_memory_barrier();
+    // Safety: Address is valid, memory barrier used.
+    let busy = unsafe { FLAG_REG.read_volatile() } & FLAG_BUSY != 0;
+    data_memory_barrier();
+    busy
+}
+
+impl<P: TxPin, C> eio::ErrorType for Writer<P, C> {
+    type Error = Infallible;
+}
+
+impl<P: TxPin, C> hal_nb::serial::ErrorType for Writer<P, C> {
+    type Error = Infallible;
+}
+
+impl<P: TxPin, C> eio::Write for Writer<P, C> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let written = transmit(buf);
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while busy() {
+            core::hint::spin_loop();
+        }
+        Ok(())
+    }
+}
+
+impl<P: TxPin, C> eio::WriteReady for Writer<P, C> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        data_memory_barrier();
+        // Safety: Address is valid, memory barrier used.
+        Ok(unsafe { FLAG_REG.read_volatile() } & FLAG_TX_FULL == 0)
+    }
+}
+
+impl<P: TxPin, C> hal_nb::serial::Write for Writer<P, C> {
+    fn write(&mut self, word: u8) -> hal_nb::nb::Result<(), Self::Error> {
+        if transmit(&[word]) == 0 {
+            return Err(hal_nb::nb::Error::WouldBlock);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> hal_nb::nb::Result<(), Self::Error> {
+        if busy() {
+            return Err(hal_nb::nb::Error::WouldBlock);
+        }
+        Ok(())
+    }
+}
+
+/// This implementation is cancel-safe.
+impl<P: TxPin, C> eio_async::Write for Writer<P, C> {
+    fn write(&mut self, buf: &[u8]) -> impl Future<Output = Result<usize, Self::Error>> {
+        WriteFut { _writer: self, buf }
+    }
+
+    /// The UART has no interrupt for the end of a transmission, so this polls once the transmit
+    /// FIFO is no longer full.
+    fn flush(&mut self) -> impl Future<Output = Result<(), Self::Error>> {
+        FlushFut { _writer: self }
+    }
+}
+
+/// Wake the current task when the transmit FIFO drains down to `Config::tx_fifo_level`.
+///
+/// The transmit interrupt only triggers when the FIFO level crosses the threshold, so this must
+/// only be used when the FIFO is full.
+fn wait(cx: &Context) {
+    critical_section::with(|cs| set_waker(&WRITER_WAKER, cx.waker(), cs));
+    super::set_interrupts(INTERRUPT_TX, true);
+}
+
+#[derive(Debug)]
+struct WriteFut<'a, 'b, P, C> {
+    _writer: &'a mut Writer<P, C>,
+    buf: &'b [u8],
+}
+
+impl<P: TxPin, C> Future for WriteFut<'_, '_, P, C> {
+    type Output = Result<usize, Infallible>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        match transmit(self.buf) {
+            0 => {
+                wait(cx);
+                Poll::Pending
+            }
+            written => Poll::Ready(Ok(written)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FlushFut<'a, P, C> {
+    _writer: &'a mut Writer<P, C>,
+}
+
+impl<P: TxPin, C> Future for FlushFut<'_, P, C> {
+    type Output = Result<(), Infallible>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        data_memory_barrier();
+        // Safety: Address is valid, memory barrier used.
+        let flags = unsafe { FLAG_REG.read_volatile() };
+        data_memory_barrier();
+        if flags & FLAG_TX_FULL != 0 {
+            wait(cx);
+            return Poll::Pending;
+        } else if flags & FLAG_BUSY != 0 {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub(super) fn interrupt_handler() {
+    super::set_interrupts(INTERRUPT_TX, false);
+    critical_section::with(|cs| wake(&WRITER_WAKER, cs));
+}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the UART TX pin.
+#[allow(private_bounds)]
+pub trait TxPin: Sealed {}
+
+impl TxPin for gpio::Pin<14, Alternate0> {}
+impl TxPin for gpio::Pin<32, Alternate3> {}
+impl TxPin for gpio::Pin<36, Alternate2> {}
+
+/// Trait that represents [`gpio::Pin`]s that are valid for use as the UART CTS pin.
+#[allow(private_bounds)]
+pub trait CtsPin: Sealed {}
+
+impl CtsPin for gpio::Pin<16, Alternate3> {}
+impl CtsPin for gpio::Pin<30, Alternate3> {}
+impl CtsPin for gpio::Pin<39, Alternate2> {}